@@ -0,0 +1,43 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::scenario::{Conditions, ScenarioReport};
+
+// belts are left uncapped while measuring, so a belt that would need to carry
+// more than the tier allows shows up as an overloaded belt instead of
+// silently throttling everything upstream of it
+pub const UNCAPPED: u64 = 1_000_000 * 100_000_000;
+
+// balancer related functions
+impl Balancer {
+    // simulate each scenario, where a scenario gives the most each output will
+    // consume (None for no limit), and report how the output rates shift
+    pub fn resilience(&self, result: &BalancerResult, scenarios: &[Vec<Option<u64>>]) -> Option<ScenarioReport> {
+        let normal = Conditions { supply: self.inputs.clone(), demand: vec![None; self.outputs.len()] };
+        let scenarios = scenarios
+            .iter()
+            .map(|demand| Conditions { demand: demand.clone(), ..normal.clone() })
+            .collect::<Vec<Conditions>>();
+        self.run_scenarios(result, normal, &scenarios)
+    }
+
+    // a scenario from (output number, rate) pairs, outputs not listed consume freely
+    pub fn blockage(&self, limits: &[(usize, f32)]) -> Vec<Option<u64>> {
+        let mut demand = vec![None; self.outputs.len()];
+        for &(output, rate) in limits {
+            if output >= 1 && output <= demand.len() {
+                demand[output - 1] = Some((rate as f64 * 1e8) as u64);
+            }
+        }
+        demand
+    }
+
+    // every output blocked on its own, one scenario each
+    pub fn single_blockages(&self) -> Vec<Vec<Option<u64>>> {
+        (0..self.outputs.len())
+            .map(|i| {
+                let mut demand = vec![None; self.outputs.len()];
+                demand[i] = Some(0);
+                demand
+            })
+            .collect()
+    }
+}
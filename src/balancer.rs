@@ -1,9 +1,10 @@
-use crate::cmd::Args;
+use crate::cmd::{Algorithm, Args, CostModel};
+use crate::graph::BalancerGraph;
 use crate::operation::Operation;
 use std::fmt::Formatter;
 use std::fmt;
 
-#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct BalancerState {
     pub values: Box<[u64]>
 }
@@ -41,34 +42,90 @@ impl fmt::Display for BalancerState {
     }
 }
 
+#[derive(Clone)]
 pub struct Balancer {
     pub max_belt: u64,
     pub inputs: Vec<u64>,
     pub outputs: Vec<u64>,
+    // names given to the inputs and outputs, in the same order
+    pub input_labels: Vec<Option<String>>,
+    pub output_labels: Vec<Option<String>>,
+    // items on each belt with their rates, empty for belts of one anonymous item
+    pub input_items: Vec<Vec<(String, u64)>>,
+    pub output_items: Vec<Vec<(String, u64)>>,
+    // lower numbers are fed first, None comes after every number
+    pub priorities: Vec<Option<u32>>,
+    pub threads: usize,
+    pub algorithm: Algorithm,
+    // what each building adds to the cost of a path
+    pub cost_model: CostModel,
+    // seconds before the search gives up
+    pub time_limit: u64,
+    // plan pipes and junctions instead of belts
+    pub fluid: bool,
+    // every output has to take the same share of every input
+    pub strict: bool,
+    // merges over the belt limit run full and back up their inputs
+    pub saturate: bool,
     // pub depth_limit: u64,
 }
 
 impl Balancer {
     pub fn new(args: Args) -> Balancer {
-        let inputs = args.inputs
-            .iter().map(|x| (*x as f64 * 1e8) as u64).collect::<Vec<u64>>();
-        let outputs = args.outputs
-            .iter().map(|x| (*x as f64 * 1e8) as u64).collect::<Vec<u64>>();
+        let input_items = scale_items(&args.input_items);
+        let output_items = scale_items(&args.output_items);
+        let inputs = scale_rates(&args.inputs, &input_items);
+        let outputs = scale_rates(&args.outputs, &output_items);
 
         Balancer {
             inputs,
             outputs,
+            input_labels: args.input_labels,
+            output_labels: args.output_labels,
+            input_items,
+            output_items,
+            priorities: args.priorities,
             max_belt: args.max_belt * 10u64.pow(8),
+            threads: args.threads,
+            algorithm: args.algorithm,
+            cost_model: args.cost_model,
+            time_limit: args.time_limit,
+            fluid: args.fluid,
+            strict: args.strict,
+            saturate: args.saturate,
             // depth_limit: 0,
         }
     }
 }
 
+// a belt of several items carries the sum of its scaled item rates, scaling
+// the f32 sum instead can round to a rate its items don't add up to
+fn scale_rates(rates: &[f32], items: &[Vec<(String, u64)>]) -> Vec<u64> {
+    rates
+        .iter()
+        .enumerate()
+        .map(|(i, x)| match items.get(i) {
+            Some(mix) if !mix.is_empty() => mix.iter().map(|(_, rate)| rate).sum(),
+            _ => (*x as f64 * 1e8) as u64,
+        })
+        .collect()
+}
+
+fn scale_items(belts: &[Vec<(String, f32)>]) -> Vec<Vec<(String, u64)>> {
+    belts
+        .iter()
+        .map(|mix| mix.iter().map(|(item, rate)| (item.clone(), (*rate as f64 * 1e8) as u64)).collect())
+        .collect()
+}
+
 pub struct BalancerResult {
     path: Vec<(Operation, BalancerState)>,
     total_states: u64,
     checked_states: u64,
     time: f64,
+    canonical: bool,
+    // set when the path alone can't be laid out unambiguously
+    graph: Option<BalancerGraph>,
 }
 
 impl BalancerResult {
@@ -77,13 +134,54 @@ impl BalancerResult {
             path,
             total_states: total,
             checked_states: checked,
-            time
+            time,
+            canonical: false,
+            graph: None,
+        }
+    }
+
+    // result taken from the canonical table rather than searched for
+    pub fn canonical(path: Vec<(Operation, BalancerState)>, time: f64) -> BalancerResult {
+        BalancerResult {
+            canonical: true,
+            ..BalancerResult::new(path, 0, 0, time)
         }
     }
 
     pub fn iter(&self) -> std::slice::Iter<(Operation, BalancerState)> {
         self.path.iter()
     }
+
+    pub fn path(&self) -> &[(Operation, BalancerState)] {
+        &self.path
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    pub fn total_states(&self) -> u64 {
+        self.total_states
+    }
+
+    pub fn checked_states(&self) -> u64 {
+        self.checked_states
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn with_graph(self, graph: BalancerGraph) -> BalancerResult {
+        BalancerResult {
+            graph: Some(graph),
+            ..self
+        }
+    }
+
+    pub fn graph(&self) -> Option<&BalancerGraph> {
+        self.graph.as_ref()
+    }
 }
 
 impl Default for BalancerResult {
@@ -94,10 +192,14 @@ impl Default for BalancerResult {
 
 impl fmt::Display for BalancerResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{} of {} states checked ({:.3}%)",
-            self.checked_states, self.total_states,
-            self.checked_states as f64 / self.total_states as f64 * 100.0
-        )?;
+        if self.canonical {
+            writeln!(f, "canonical balancer, no search needed")?;
+        } else {
+            writeln!(f, "{} of {} states checked ({:.3}%)",
+                self.checked_states, self.total_states,
+                self.checked_states as f64 / self.total_states as f64 * 100.0
+            )?;
+        }
         for (i, (op, state))in self.iter().enumerate() {
             writeln!(f, "{}. {} => {}", i + 1, op, state)?;
         }
@@ -0,0 +1,208 @@
+use crate::balancer::{Balancer, BalancerResult, BalancerState};
+use crate::cmd::{Algorithm, Args, CostModel};
+use crate::graph::BalancerGraph;
+use crate::operation::Operation;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Instant;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Solved,
+    // the search ran out of states or time
+    NoSolution,
+    Unbalanced,
+    Invalid(String),
+}
+
+pub struct BatchEntry {
+    // line of the batch file the problem is on
+    pub line: usize,
+    pub problem: String,
+    pub status: Status,
+    pub buildings: usize,
+    pub time: f64,
+    pub checked: u64,
+    // solved earlier in the batch, the numbers are from that run
+    pub cached: bool,
+}
+
+pub struct BatchReport {
+    pub entries: Vec<BatchEntry>,
+    pub time: f64,
+}
+
+// sorted input and output rates with every setting the search result
+// depends on: belt limit, time limit, algorithm, cost model and threads
+type Key = (Vec<u64>, Vec<u64>, u64, u64, Algorithm, CostModel, usize);
+
+// what a finished problem leaves in the cache
+#[derive(Clone)]
+struct Solved {
+    status: Status,
+    buildings: usize,
+    time: f64,
+    checked: u64,
+    graph: Option<BalancerGraph>,
+    // to lay out the same balancer for belts given in another order
+    path: Vec<(Operation, BalancerState)>,
+}
+
+// solves every problem in a batch file, one `Args` style line each with `#`
+// starting a comment. `defaults` go in front of every line like session
+// settings do. a plain problem is only searched once for the same rates,
+// belt, time limit, algorithm, cost model and threads, fluid, strict,
+// saturated, sorting and priority plans are always searched. `-save` on a line writes
+// that balancer out
+pub fn run_batch(text: &str, defaults: &str) -> BatchReport {
+    let start = Instant::now();
+    let mut cache: HashMap<Key, Solved> = HashMap::new();
+    let mut entries = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let problem = line.split('#').next().unwrap_or("").trim();
+        if problem.is_empty() {
+            continue;
+        }
+        let mut entry = BatchEntry {
+            line: i + 1,
+            problem: problem.to_string(),
+            status: Status::Solved,
+            buildings: 0,
+            time: 0.,
+            checked: 0,
+            cached: false,
+        };
+
+        let args = Args::new(format!("{} {}", defaults, problem));
+        if args.bench || args.quit || args.batch.is_some() || args.network.is_some() || args.load.is_some() {
+            entry.status = Status::Invalid("only balancer problems can be batched".to_string());
+            entries.push(entry);
+            continue;
+        }
+        if args.inputs.is_empty() || args.outputs.is_empty() {
+            entry.status = Status::Invalid("needs input and output belts".to_string());
+            entries.push(entry);
+            continue;
+        }
+
+        let save = args.save.clone();
+        let balancer = Balancer::new(args);
+        let mut key: Key = (
+            balancer.inputs.clone(),
+            balancer.outputs.clone(),
+            balancer.max_belt,
+            balancer.time_limit,
+            balancer.algorithm,
+            balancer.cost_model,
+            balancer.threads,
+        );
+        key.0.sort();
+        key.1.sort();
+
+        // fluid, strict, saturated, sorting and priority plans differ for the same rates
+        let plain = !balancer.fluid && !balancer.strict && !balancer.saturate && !balancer.is_sushi() && !balancer.is_priority();
+
+        let solved = match cache.get(&key).filter(|_| plain) {
+            Some(solved) => {
+                entry.cached = true;
+                let mut solved = solved.clone();
+                solved.graph = balancer.graph(&BalancerResult::new(solved.path.clone(), 0, 0, 0.));
+                solved
+            }
+            None => {
+                let solved = solve(&balancer);
+                if plain {
+                    cache.insert(key, solved.clone());
+                }
+                solved
+            }
+        };
+        entry.status = solved.status;
+        entry.buildings = solved.buildings;
+        entry.time = solved.time;
+        entry.checked = solved.checked;
+
+        if let (Some(file), Some(graph)) = (save, &solved.graph) {
+            if let Err(error) = std::fs::write(&file, format!("{}\n", graph)) {
+                entry.status = Status::Invalid(format!("could not write {}: {}", file, error));
+            }
+        }
+        entries.push(entry);
+    }
+
+    BatchReport {
+        entries,
+        time: start.elapsed().as_secs_f64(),
+    }
+}
+
+fn solve(balancer: &Balancer) -> Solved {
+    let (supply, demand) = (balancer.inputs.iter().sum::<u64>(), balancer.outputs.iter().sum::<u64>());
+    // the same rule as the search, saturated merges can throttle a surplus away
+    if supply != demand && !(balancer.saturate && supply > demand) {
+        return Solved {
+            status: Status::Unbalanced,
+            buildings: 0,
+            time: 0.,
+            checked: 0,
+            graph: None,
+            path: vec![],
+        };
+    }
+
+    let result: BalancerResult = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result);
+    let mut inputs = balancer.inputs.clone();
+    let mut outputs = balancer.outputs.clone();
+    inputs.sort();
+    outputs.sort();
+    // an empty path only solves a problem whose outputs are already its inputs
+    let status = if graph.is_some() && (!result.path().is_empty() || inputs == outputs) {
+        Status::Solved
+    } else {
+        Status::NoSolution
+    };
+
+    Solved {
+        status,
+        buildings: result.path().len(),
+        time: result.time(),
+        checked: result.checked_states(),
+        graph,
+        path: result.path().to_vec(),
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>5}  {:<12} {:>9} {:>11} {:>10}  problem", "line", "status", "buildings", "time", "checked")?;
+        for entry in &self.entries {
+            let status = match &entry.status {
+                Status::Solved if entry.cached => "cached",
+                Status::Solved => "solved",
+                Status::NoSolution => "no solution",
+                Status::Unbalanced => "unbalanced",
+                Status::Invalid(_) => "invalid",
+            };
+            write!(f, "{:>5}  {:<12} {:>9} {:>10.4}s {:>10}  {}",
+                entry.line, status, entry.buildings, entry.time, entry.checked, entry.problem
+            )?;
+            if let Status::Invalid(reason) = &entry.status {
+                write!(f, " ({})", reason)?;
+            }
+            writeln!(f)?;
+        }
+
+        let count = |status: Status| self.entries.iter().filter(|e| e.status == status).count();
+        write!(f, "{} problems: {} solved, {} without a solution, {} unbalanced, {} invalid in {:.3}s",
+            self.entries.len(),
+            count(Status::Solved),
+            count(Status::NoSolution),
+            count(Status::Unbalanced),
+            self.entries.iter().filter(|e| matches!(e.status, Status::Invalid(_))).count(),
+            self.time
+        )
+    }
+}
@@ -0,0 +1,145 @@
+use crate::balancer::{Balancer, BalancerState};
+use crate::cmd::Args;
+use crate::intern::{StateArena, StateId};
+use crate::operation::Operation;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Instant;
+
+// the system allocator keeping count of the bytes currently allocated, so
+// both state layouts are measured by what they really take from the heap.
+// only built with the bench feature so other runs don't pay for the counting
+#[cfg(feature = "bench")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: Counting = Counting;
+
+    pub fn allocated() -> Option<usize> {
+        Some(ALLOCATED.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(not(feature = "bench"))]
+mod counting {
+    pub fn allocated() -> Option<usize> {
+        None
+    }
+}
+
+// seconds `build` takes and the heap bytes still held by what it returns,
+// None without the bench feature
+fn measure<T>(build: impl FnOnce() -> T) -> (f64, Option<usize>, T) {
+    let before = counting::allocated();
+    let start = Instant::now();
+    let built = build();
+    let elapsed = start.elapsed().as_secs_f64();
+    let bytes = counting::allocated().zip(before).map(|(after, before)| after.saturating_sub(before));
+    (elapsed, bytes, built)
+}
+
+// a mix of problems that solve quickly and ones that run into the time limit
+const PROBLEMS: [&str; 5] = [
+    "-in 480 -out 100 100 100 90 90 -tl 5",
+    "-in 720 -out 100 100 100 90 90 120 120 -tl 5",
+    "-in 600 600 -out 100 200 300 150 250 200 -tl 5",
+    "-in 600 -out 100 100 100 90 90 120 -tl 5",
+    "-in 450 450 -out 100 100 100 120 120 120 80 80 80 -tl 5",
+];
+
+// runs the sequential a* on each problem, then stores the states it found
+// again both interned and as boxed map keys with a cloned parent vec, the
+// layout used before interning, and reports how fast each stores them and
+// how much heap each one takes
+pub fn run_benchmarks() {
+    for problem in PROBLEMS {
+        let balancer = Balancer::new(Args::new(problem.to_string()));
+        let start = Instant::now();
+        let (result, arena) = balancer.find_ideal_balance_astar(start);
+        let elapsed = start.elapsed().as_secs_f64();
+
+        // the stored states again in both layouts, each with one cost
+        // and one parent entry per state as the search keeps them
+        let stored = arena.len().max(1);
+        let states = (0..arena.len() as StateId).map(|id| arena.get(id)).collect::<Vec<BalancerState>>();
+        let (interned_time, interned, _) = measure(|| {
+            let mut ids = StateArena::new(balancer.search_gcd());
+            let mut cost = HashMap::new();
+            let mut parent = HashMap::new();
+            for state in &states {
+                let id = ids.intern(state);
+                cost.entry(id).or_insert(0i64);
+                parent.entry(id).or_insert((None::<Operation>, Some(id)));
+            }
+            (ids, cost, parent)
+        });
+        let (boxed_time, boxed, _) = measure(|| {
+            let mut cost = HashMap::new();
+            let mut parent = HashMap::new();
+            for state in &states {
+                cost.entry(state.clone()).or_insert(0i64);
+                parent.entry(state.clone()).or_insert((None::<Operation>, Some(state.to_vec())));
+            }
+            (cost, parent)
+        });
+
+        println!("{}", problem);
+        println!("  {} states found in {:.3}s ({:.0} states/s), {} expanded",
+            arena.len(),
+            elapsed,
+            arena.len() as f64 / elapsed,
+            result.checked_states()
+        );
+        println!("  stored interned at {:.0} states/s, boxed at {:.0} states/s",
+            stored as f64 / interned_time,
+            stored as f64 / boxed_time
+        );
+        match interned.zip(boxed) {
+            Some((interned, boxed)) => println!("  {} bytes/state interned, {} bytes/state boxed",
+                interned / stored,
+                boxed / stored
+            ),
+            None => println!("  build with --features bench to measure bytes/state"),
+        }
+    }
+
+    // the parallel search with one thread against two workers per core, or
+    // at least two, so the pool is used even on a single core
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = (cores * 2).max(4);
+    println!("parallel search, 1 thread against {} on {} cores", threads, cores);
+    for problem in PROBLEMS {
+        let timings = [1, threads].map(|threads| {
+            let mut balancer = Balancer::new(Args::new(problem.to_string()));
+            balancer.threads = threads;
+            let start = Instant::now();
+            let result = balancer.find_ideal_balance_parallel(start);
+            (start.elapsed().as_secs_f64(), result.checked_states())
+        });
+        println!("{}", problem);
+        for (t, (elapsed, checked)) in [1, threads].iter().zip(timings) {
+            println!("  {:>2} threads: {} expanded in {:.3}s ({:.0} states/s)", t, checked, elapsed, checked as f64 / elapsed);
+        }
+        // states/s rather than time, the slow problems run to the time limit either way
+        let rate = |(elapsed, checked): (f64, u64)| checked as f64 / elapsed;
+        println!("  {:.2}x the states/s", rate(timings[1]) / rate(timings[0]));
+    }
+}
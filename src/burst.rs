@@ -0,0 +1,134 @@
+use crate::backpressure::UNCAPPED;
+use crate::balancer::{Balancer, BalancerResult};
+use crate::sim::Simulator;
+use crate::strict::contribution_matrix;
+use std::fmt;
+use std::fmt::Formatter;
+
+// an input that unloads in bursts, like a freight platform or a truck
+// station, rates scaled by 1e8 and the buffer in items
+pub struct BurstSource {
+    pub input: usize,
+    pub average: u64,
+    pub peak: u64,
+    pub buffer: u64,
+}
+
+impl BurstSource {
+    // how long one burst lasts and how long a full cycle takes at the average rate
+    pub fn minutes(&self) -> (f64, f64) {
+        let items = self.buffer as f64 / 1e8;
+        (items / (self.peak as f64 / 1e8), items / (self.average as f64 / 1e8))
+    }
+}
+
+pub struct BurstReport {
+    pub input_names: Vec<String>,
+    pub output_names: Vec<String>,
+    pub sources: Vec<BurstSource>,
+    pub requested: Vec<u64>,
+    // output rates with every bursting input unloading at its peak
+    pub peak: Vec<u64>,
+    // items each output has to buffer to pass a burst on at its average rate
+    pub buffers: Vec<f64>,
+    // belts over the belt limit during a burst, as (from, to, rate)
+    pub overloaded: Vec<(String, String, u64)>,
+    pub max_belt: u64,
+}
+
+// balancer related functions
+impl Balancer {
+    // sources from (input number, peak rate, buffer size), the -in rate of an
+    // input is its average. an input only bursts one way
+    pub fn burst_sources(&self, bursts: &[(usize, f32, f32)]) -> Result<Vec<BurstSource>, String> {
+        bursts
+            .iter()
+            .enumerate()
+            .map(|(i, &(input, peak, buffer))| {
+                let Some(&average) = self.inputs.get(input.wrapping_sub(1)) else {
+                    return Err(format!("There is no input {} to burst", input));
+                };
+                if bursts[..i].iter().any(|&(other, _, _)| other == input) {
+                    return Err(format!("Input {} is given more than one burst", input));
+                }
+                let peak = (peak as f64 * 1e8) as u64;
+                if peak < average {
+                    return Err(format!("Input {} can't peak at {}, below its average of {}",
+                        input, peak as f64 / 1e8, average as f64 / 1e8
+                    ));
+                }
+                Ok(BurstSource { input, average, peak, buffer: (buffer as f64 * 1e8) as u64 })
+            })
+            .collect()
+    }
+
+    // simulate the plan with every bursting input unloading at its peak and
+    // the belts left uncapped, so belts a burst pushes over the limit show up.
+    // what an output gets over its rate during a burst is the buffering it
+    // needs downstream, worked out from its exact share of each input
+    pub fn bursts(&self, result: &BalancerResult, sources: Vec<BurstSource>) -> Option<BurstReport> {
+        let graph = self.graph(result)?;
+        let mut sim = Simulator::new(&graph, UNCAPPED);
+        for source in &sources {
+            sim.supply[source.input - 1] = source.peak;
+        }
+        let flow = sim.run(600, 6000);
+
+        let overloaded = graph.edges
+            .iter()
+            .zip(&flow.edges)
+            .filter(|(_, &rate)| rate > self.max_belt)
+            .map(|(edge, &rate)| (graph.nodes[edge.from].name(), graph.nodes[edge.to].name(), rate))
+            .collect();
+
+        let matrix = contribution_matrix(&graph);
+        let mut buffers = vec![0.0; self.outputs.len()];
+        for source in &sources {
+            let (burst, _) = source.minutes();
+            let extra = (source.peak - source.average) as f64 / 1e8 * burst;
+            let average = source.average as f64 / 1e8;
+            for (buffer, share) in buffers.iter_mut().zip(&matrix[source.input - 1]) {
+                *buffer += share.to_f64() / average * extra;
+            }
+        }
+
+        Some(BurstReport {
+            input_names: self.input_names(),
+            output_names: self.output_names(),
+            sources,
+            requested: self.outputs.clone(),
+            peak: flow.outputs,
+            buffers,
+            overloaded,
+            max_belt: self.max_belt,
+        })
+    }
+}
+
+impl fmt::Display for BurstReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bursts:")?;
+        for source in &self.sources {
+            let (burst, cycle) = source.minutes();
+            writeln!(f, "  {}: {:.2} for {:.1} min out of every {:.1}, {:.0} items buffered",
+                self.input_names[source.input - 1], source.peak as f64 / 1e8, burst, cycle, source.buffer as f64 / 1e8
+            )?;
+        }
+        for (((name, requested), peak), buffer) in self.output_names.iter().zip(&self.requested).zip(&self.peak).zip(&self.buffers) {
+            write!(f, "  {}: {:.2} -> {:.2} during a burst", name, *requested as f64 / 1e8, *peak as f64 / 1e8)?;
+            if *buffer > 0.0 {
+                write!(f, ", buffer {:.0} items", buffer.ceil())?;
+            }
+            writeln!(f)?;
+        }
+        for (from, to, rate) in &self.overloaded {
+            writeln!(f, "  {} -> {} carries {:.2}, over the {:.2} belt limit", from, to, *rate as f64 / 1e8, self.max_belt as f64 / 1e8)?;
+        }
+
+        if self.overloaded.is_empty() {
+            write!(f, "every belt stays within {:.2} during bursts", self.max_belt as f64 / 1e8)
+        } else {
+            write!(f, "{} belts overload during bursts, bursts back up into the buffers", self.overloaded.len())
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::balancer::{Balancer, BalancerResult, BalancerState};
-use crate::helpers::{gcd, gcd_vec, merges, multiset, rev_merges, rev_splits, splits, validate_state};
+use crate::cmd::{Algorithm, CostModel};
+use crate::fluid::fluid_splits;
+use crate::intern::{StateArena, StateId};
+use crate::helpers::{apply, gcd, gcd_vec, merges, multiset, rev_merges, rev_splits, splits, validate_state, Combinations};
 use crate::operation::{Operation, ReverseOperation};
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap};
@@ -7,7 +10,7 @@ use std::time::Instant;
 
 // balancer related functions
 impl Balancer {
-    fn heuristic(state: &Vec<u64>, target: &Vec<u64>, gcd: u64) -> i64 {
+    pub fn heuristic(state: &Vec<u64>, target: &Vec<u64>, gcd: u64) -> i64 {
         let mut state_counts = HashMap::new();
         let mut target_counts = HashMap::new();
 
@@ -46,39 +49,45 @@ impl Balancer {
         estimated_cost
     }
 
-    fn get_states_fwd(&self, state: &Vec<u64>, gcd: u64) -> Vec<(Operation, BalancerState)> {
-        let mut next_states = vec![];
-
-        let split_states = splits(&state.clone())
-            .iter()
-            .filter(|(_, state)| validate_state(state, gcd))
-            .cloned()
-            .collect::<Vec<(Operation, BalancerState)>>();
-
-        let merge_states = merges(&state)
-            .iter()
-            .filter(|(_, state)| validate_state(state, gcd))
-            .cloned()
-            .collect::<Vec<(Operation, BalancerState)>>();
-
-        // splits
-        for (action, split) in split_states {
-            if *split.iter().max().unwrap_or(&(self.max_belt + 1)) <= self.max_belt {
-                next_states.push((action, split));
-            }
+    // every rate the search can make is a multiple of this, saturated belts
+    // carry the belt limit so it has to divide that too
+    pub fn search_gcd(&self) -> u64 {
+        let rates = gcd(gcd_vec(self.outputs.clone()), gcd_vec(self.inputs.clone()));
+        match self.saturate {
+            true => gcd(rates, self.max_belt),
+            false => rates,
         }
+    }
 
-        // merges
-        for (action, merged) in merge_states {
-            if *merged.iter().max().unwrap_or(&(self.max_belt + 1)) <= self.max_belt {
-                next_states.push((action, multiset(merged.to_vec())));
+    // successors are produced lazily, an operation is checked against the belt
+    // limit and gcd before the state it leads to is built
+    pub fn get_states_fwd<'a>(&'a self, state: &'a [u64], gcd: u64) -> impl Iterator<Item = (Operation, BalancerState)> + 'a {
+        let fits = move |val: u64| val >= gcd && val.is_multiple_of(gcd) && val <= self.max_belt;
+        // values carried over unchanged only need checking again if the parent had a bad one
+        let parent_fits = state.iter().all(|&val| fits(val));
+
+        let fluid = self.fluid.then(|| fluid_splits(state, &self.outputs));
+        // a merge over the belt limit gives a full belt, the rest backs up
+        let saturated = self.saturate.then(|| merges(state).filter_map(move |op| match op {
+            Operation::Merge { input, output } if output > self.max_belt => {
+                Some(Operation::Merge { input, output: self.max_belt })
             }
-        }
-
-        next_states
+            _ => None,
+        }));
+
+        splits(state)
+            .chain(merges(state))
+            .chain(fluid.into_iter().flatten())
+            .chain(saturated.into_iter().flatten())
+            .filter(move |op| op.outputs().into_iter().flatten().all(fits))
+            .map(move |op| {
+                let next = apply(state, &op);
+                (op, next)
+            })
+            .filter(move |(_, next)| parent_fits || next.iter().all(|&val| fits(val)))
     }
 
-    fn get_states_bkwd(&self, state: &Vec<u64>, gcd: u64) -> Vec<(ReverseOperation, BalancerState)> {
+    pub fn get_states_bkwd(&self, state: &Vec<u64>, gcd: u64) -> Vec<(ReverseOperation, BalancerState)> {
         let mut next_states = vec![];
 
         let split_states = rev_splits(&state, gcd)
@@ -107,15 +116,30 @@ impl Balancer {
             }
         }
 
+        // uneven junction splits, any 2 or 3 pipes joined back up
+        if self.fluid {
+            for input in (2..=3).flat_map(|k| Combinations::new(state, k)) {
+                let parts = [input.0, input.1, input.2];
+                let output = parts.iter().flatten().sum::<u64>();
+                if parts.iter().flatten().all(|&x| Some(x) == input.0) || output > self.max_belt {
+                    continue;
+                }
+                let joined = apply(state, &Operation::Merge { input, output });
+                next_states.push((ReverseOperation::Merge { input, output }, joined));
+            }
+        }
+
         next_states
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn build_path(
-        meeting_point: &BalancerState,
-        initial_state: &BalancerState,
-        target_state: &BalancerState,
-        from_fwd: HashMap<BalancerState, (Option<Operation>, Option<Vec<u64>>)>,
-        from_bkwd: HashMap<BalancerState, (Option<ReverseOperation>, Option<Vec<u64>>)>,
+        meeting_point: StateId,
+        initial_state: StateId,
+        target_state: StateId,
+        arena: &StateArena,
+        from_fwd: HashMap<StateId, (Option<Operation>, Option<StateId>)>,
+        from_bkwd: HashMap<StateId, (Option<ReverseOperation>, Option<StateId>)>,
         total_states: u64,
         checked_states: u64,
         time: f64,
@@ -123,13 +147,13 @@ impl Balancer {
     {
         let mut path = vec![];
 
-        let mut current = meeting_point.clone();
+        let mut current = meeting_point;
         let mut fwd = vec![];
 
-        while current != *initial_state {
+        while current != initial_state {
             if let Some((Some(op), Some(parent))) = from_fwd.get(&current) {
-                fwd.push((op.clone(), current.clone()));
-                current = multiset(parent.clone());
+                fwd.push((op.clone(), arena.get(current)));
+                current = *parent;
                 continue;
             }
             break;
@@ -137,11 +161,11 @@ impl Balancer {
         fwd.reverse();
         path.extend(fwd);
 
-        current = meeting_point.clone();
-        while current != *target_state {
+        current = meeting_point;
+        while current != target_state {
             if let Some((Some(op), Some(parent))) = from_bkwd.get(&current) {
-                path.push((op.clone().forward(), multiset(parent.clone())));
-                current = multiset(parent.clone());
+                path.push((op.clone().forward(), arena.get(*parent)));
+                current = *parent;
                 continue;
             }
             break;
@@ -151,8 +175,15 @@ impl Balancer {
     }
 
     pub fn find_ideal_balance(&self) -> BalancerResult {
-        let start = Instant::now();
-        if self.inputs.iter().sum::<u64>() != self.outputs.iter().sum::<u64>() {
+        self.find_ideal_balance_since(Instant::now())
+    }
+
+    // the search with its time limit counted from `start`, so the searches
+    // a strict, sorting or priority design runs share one deadline
+    pub fn find_ideal_balance_since(&self, start: Instant) -> BalancerResult {
+        let (supply, demand) = (self.inputs.iter().sum::<u64>(), self.outputs.iter().sum::<u64>());
+        // saturated merges can throttle a surplus away, never make up a shortage
+        if supply != demand && !(self.saturate && supply > demand) {
             println!("Unbalanced I/O. Kick rocks kid");
             println!("{} in != {} out",
                      self.inputs.iter().sum::<u64>(),
@@ -161,45 +192,83 @@ impl Balancer {
             return BalancerResult::default();
         }
 
+        // the other designs and the table all pass on every input item
+        if supply == demand {
+            if self.is_sushi() {
+                return self.find_sushi_balance(start);
+            }
+            if self.is_priority() {
+                return self.find_priority_balance(start);
+            }
+            if self.strict {
+                return self.find_strict_balance(start);
+            }
+
+            // the table only holds even splits with the fewest buildings, a
+            // fluid search or another cost model can do better
+            if !self.fluid && self.cost_model == CostModel::Buildings {
+                if let Some(result) = self.canonical() {
+                    return result;
+                }
+            }
+        }
+
+        if self.algorithm == Algorithm::IdaStar {
+            return self.find_ideal_balance_ida(start);
+        }
+
+        if self.threads > 1 {
+            return self.find_ideal_balance_parallel(start);
+        }
+
+        self.find_ideal_balance_astar(start).0
+    }
+
+    // bidirectional a*, also returns the arena so callers can inspect its memory use
+    pub fn find_ideal_balance_astar(&self, start: Instant) -> (BalancerResult, StateArena) {
         let initial_state = multiset(self.inputs.clone());
         let target_state = multiset(self.outputs.clone());
-        let gcd = gcd(
-            gcd_vec(self.outputs.clone()), gcd_vec(self.inputs.clone())
-        );
+        let gcd = self.search_gcd();
+
+        let mut arena = StateArena::new(gcd);
+        let initial_id = arena.intern(&initial_state);
+        let target_id = arena.intern(&target_state);
+        let initial = initial_state.to_vec();
+        let target = target_state.to_vec();
 
-        /// forward traversal data
-        let initial_h = Self::heuristic(&initial_state.to_vec(), &target_state.to_vec(), gcd);
+        // forward traversal data
+        let initial_h = Self::heuristic(&initial, &target, gcd);
 
         let mut frontier_fwd = BinaryHeap::new();
-        frontier_fwd.push((Reverse(initial_h), 0i64, initial_state.clone()));
+        frontier_fwd.push((Reverse(initial_h), 0i64, initial_id));
 
         let mut cost_fwd = HashMap::new();
-        cost_fwd.insert(initial_state.clone(), 0i64);
+        cost_fwd.insert(initial_id, 0i64);
 
-        let mut from_fwd: HashMap<BalancerState, (Option<Operation>, Option<Vec<u64>>)> = HashMap::new();
-        from_fwd.insert(initial_state.clone(), (None, None));
+        let mut from_fwd: HashMap<StateId, (Option<Operation>, Option<StateId>)> = HashMap::new();
+        from_fwd.insert(initial_id, (None, None));
 
-        /// reverse traversal data
-        let target_h = Self::heuristic(&target_state.to_vec(), &initial_state.to_vec(), gcd);
+        // reverse traversal data
+        let target_h = Self::heuristic(&target, &initial, gcd);
 
         let mut frontier_bkwd = BinaryHeap::new();
-        frontier_bkwd.push((Reverse(target_h), 0i64, target_state.clone()));
+        frontier_bkwd.push((Reverse(target_h), 0i64, target_id));
 
         let mut cost_bkwd = HashMap::new();
-        cost_bkwd.insert(target_state.clone(), 0i64);
+        cost_bkwd.insert(target_id, 0i64);
 
-        let mut from_bkwd: HashMap<BalancerState, (Option<ReverseOperation>, Option<Vec<u64>>)> = HashMap::new();
-        from_bkwd.insert(target_state.clone(), (None, None));
+        let mut from_bkwd: HashMap<StateId, (Option<ReverseOperation>, Option<StateId>)> = HashMap::new();
+        from_bkwd.insert(target_id, (None, None));
 
-        /// main traversal loop
+        // main traversal loop
         let mut states_checked = 0u64;
         let mut total_states = 0u64;
         let mut fwd = true;
         let mut best_confidence = i64::MAX;
-        let mut meeting_point: Option<BalancerState> = None;
+        let mut meeting_point: Option<StateId> = None;
 
         while (!frontier_fwd.is_empty() || !frontier_bkwd.is_empty()) &&
-            start.elapsed().as_secs() < 30 {
+            start.elapsed().as_secs() < self.time_limit {
             states_checked += 1;
 
             if fwd && !frontier_fwd.is_empty(){
@@ -209,23 +278,24 @@ impl Balancer {
                     let total_cost = cost + rev_cost;
                     if total_cost < best_confidence {
                         best_confidence = total_cost;
-                        meeting_point = Some(current.clone());
+                        meeting_point = Some(current);
                     }
                 }
 
                 // get next forward states
-                let next_states = self.get_states_fwd(&current.clone().to_vec(), gcd);
-                total_states += next_states.len() as u64;
+                let values = arena.to_vec(current);
 
-                for (action, next) in next_states {
-                    let new_cost = cost + action.cost();
+                for (action, next) in self.get_states_fwd(&values, gcd) {
+                    total_states += 1;
+                    let new_cost = cost + action.cost(self.cost_model);
+                    let next_id = arena.intern(&next);
 
-                    if cost_fwd.get(&next).map_or(true, |&prev_cost| new_cost < prev_cost) {
-                        cost_fwd.insert(next.clone(), new_cost);
-                        from_fwd.insert(next.clone(), (Some(action.clone()), Some(current.clone().to_vec())));
+                    if cost_fwd.get(&next_id).is_none_or(|&prev_cost| new_cost < prev_cost) {
+                        cost_fwd.insert(next_id, new_cost);
+                        from_fwd.insert(next_id, (Some(action), Some(current)));
 
-                        let confidence = Self::heuristic(&next.to_vec(), &target_state.to_vec(), gcd) + cost;
-                        frontier_fwd.push((Reverse(confidence), new_cost, next.clone()));
+                        let confidence = Self::heuristic(&next.to_vec(), &target, gcd) + cost;
+                        frontier_fwd.push((Reverse(confidence), new_cost, next_id));
                     }
                 }
             } else if !frontier_bkwd.is_empty(){
@@ -235,22 +305,23 @@ impl Balancer {
                     let total_confidence = cost + fwd_cost;
                     if total_confidence < best_confidence {
                         best_confidence = total_confidence;
-                        meeting_point = Some(current.clone());
+                        meeting_point = Some(current);
                     }
                 }
 
                 // get next backward states
-                let next_states = self.get_states_bkwd(&current.clone().to_vec(), gcd);
+                let next_states = self.get_states_bkwd(&arena.to_vec(current), gcd);
                 total_states += next_states.len() as u64;
                 for (action, next) in next_states {
-                    let new_cost = cost + action.cost();
+                    let new_cost = cost + action.cost(self.cost_model);
+                    let next_id = arena.intern(&next);
 
-                    if cost_bkwd.get(&next).map_or(true, |&prev_cost| new_cost < prev_cost) {
-                        cost_bkwd.insert(next.clone(), new_cost);
-                        from_bkwd.insert(next.clone(), (Some(action.clone()), Some(current.clone().to_vec())));
+                    if cost_bkwd.get(&next_id).is_none_or(|&prev_cost| new_cost < prev_cost) {
+                        cost_bkwd.insert(next_id, new_cost);
+                        from_bkwd.insert(next_id, (Some(action), Some(current)));
 
-                        let confidence = Self::heuristic(&next.to_vec(), &initial_state.to_vec(), gcd) + cost;
-                        frontier_bkwd.push((Reverse(confidence), new_cost, next.clone()));
+                        let confidence = Self::heuristic(&next.to_vec(), &initial, gcd) + cost;
+                        frontier_bkwd.push((Reverse(confidence), new_cost, next_id));
                     }
                 }
             }
@@ -265,19 +336,20 @@ impl Balancer {
 
         if let Some(meeting) = meeting_point {
             let path = Self::build_path(
-                &meeting,
-                &initial_state,
-                &target_state,
+                meeting,
+                initial_id,
+                target_id,
+                &arena,
                 from_fwd,
                 from_bkwd,
                 total_states,
                 states_checked,
                 start.elapsed().as_secs_f64()
             );
-            return path;
+            return (path, arena);
         }
 
         println!("No Solution Found after {}s :(", start.elapsed().as_secs());
-        BalancerResult::default()
+        (BalancerResult::default(), arena)
     }
-}
\ No newline at end of file
+}
@@ -1,19 +1,133 @@
+use crate::fluid::{PIPE_MK1, PIPE_MK2};
+use crate::machines;
+use crate::machines::{consumer_outputs, miner_inputs, Consumer, Miner};
+use crate::network::is_label;
 use std::fmt::{Debug, Formatter};
 use std::io;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    // bidirectional a*, fastest but keeps every visited state in memory
+    AStar,
+    // iterative deepening a*, only keeps the current path in memory
+    IdaStar,
+}
+
+// what the search counts as the cost of a building
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CostModel {
+    // every splitter and merger counts the same, the fewest buildings win
+    Buildings,
+    // a splitter counts as two mergers, for when splitters are scarcer
+    Splitters,
+    // a merger counts as two splitters
+    Mergers,
+}
+
 pub struct Args {
     pub inputs: Vec<f32>,
     pub outputs: Vec<f32>,
+    // names given like copper_a=240, in the same order as the belts. miners
+    // and consumers come after the labelled belts and have none
+    pub input_labels: Vec<Option<String>>,
+    pub output_labels: Vec<Option<String>>,
+    // item rates of mixed belts given like iron:60+copper:30, in the same
+    // order as the belts. belts given as a plain rate carry no items
+    pub input_items: Vec<Vec<(String, f32)>>,
+    pub output_items: Vec<Vec<(String, f32)>>,
+    // priority of each output given like 120!1, lower numbers are fed first
+    // and outputs without one come last
+    pub priorities: Vec<Option<u32>>,
+    // miners whose rates become inputs, capped by the belt limit
+    pub miners: Vec<Miner>,
+    // machines whose rates become outputs
+    pub consumers: Vec<Consumer>,
+    // input rate the outputs don't take when consumers are given, a positive
+    // surplus is added as one more output
+    pub surplus: f32,
     pub max_belt: u64,
+    pub threads: usize,
+    pub algorithm: Algorithm,
+    pub cost_model: CostModel,
+    pub time_limit: u64,
+    pub bench: bool,
+    pub simulate: bool,
+    // compare the result with a manifold for the same belts
+    pub manifold: bool,
+    // plan pipes and junctions, max_belt is then the pipe capacity
+    pub fluid: bool,
+    // every output draws the same share of every input
+    pub strict: bool,
+    // merges may go over the belt limit, the merged belt runs full and backs
+    // up its inputs, so inputs may offer more than the outputs take
+    pub saturate: bool,
+    // place the result on the foundation grid
+    pub layout: bool,
+    // most foundations a floor may take, as (width, height), the layout
+    // climbs over floors to fit
+    pub floor: Option<(usize, usize)>,
+    // outputs consuming less than requested, as (output number, rate),
+    // an empty list blocks each output in turn
+    pub block: Option<Vec<(usize, f32)>>,
+    // inputs delivering less than requested, as (input number, rate),
+    // an empty list stops each input in turn
+    pub starve: Option<Vec<(usize, f32)>>,
+    // inputs that unload in bursts like freight platforms, as (input number,
+    // peak rate, buffer size in items), their -in rate is the average
+    pub bursts: Vec<(usize, f32, f32)>,
+    // a hand built network in the text format, given inline after -net
+    pub network: Option<String>,
+    // file to read a hand built network from
+    pub load: Option<String>,
+    // file to write the solved balancer to
+    pub save: Option<String>,
+    // file to draw the solved balancer to as an svg
+    pub svg: Option<String>,
+    // file of problems to solve one after another
+    pub batch: Option<String>,
+    // file to write the batch report to
+    pub report: Option<String>,
     pub quit: bool,
 }
 
 impl Args {
     pub fn new(input: String) -> Self {
+        // network text has its own syntax, so -net takes the rest of the line
+        let (input, network) = match split_network(&input) {
+            (head, Some(text)) => (head.to_string(), Some(text.to_string())),
+            (_, None) => (input, None),
+        };
         let mut opts: Vec<&str> = input.split_whitespace().rev().collect();
         let mut inputs = vec![];
         let mut outputs = vec![];
+        let mut input_labels = vec![];
+        let mut output_labels = vec![];
+        let mut input_items = vec![];
+        let mut output_items = vec![];
+        let mut priorities = vec![];
+        let mut miners = vec![];
+        let mut consumers = vec![];
         let mut max_belt = 1200; // assume mk6 by default
+        let mut threads = 1;
+        let mut algorithm = Algorithm::AStar;
+        let mut cost_model = CostModel::Buildings;
+        let mut time_limit = 30;
+        let mut bench = false;
+        let mut simulate = false;
+        let mut layout = false;
+        let mut floor = None;
+        let mut manifold = false;
+        let mut fluid = false;
+        let mut strict = false;
+        let mut saturate = false;
+        let mut block = None;
+        let mut starve = None;
+        let mut bursts = vec![];
+        let mut load = None;
+        let mut save = None;
+        let mut svg = None;
+        let mut batch = None;
+        let mut report = None;
         let mut quit = false;
 
         while opts.len() > 0 {
@@ -21,37 +135,186 @@ impl Args {
                 "-in" => {
                     opts.pop();
                     // consume all non flags
-                    while opts.last().unwrap_or(&"").find("-").is_none() {
-                        let belt = opts.pop().unwrap_or("").to_lowercase();
-                        let compound = belt.split("x").collect::<Vec<&str>>();
-                        if compound.len() == 2 {
-                            let rate = compound[0].parse::<f32>().unwrap_or(0.0);
-                            let rep = compound[1].parse::<usize>().unwrap_or(0);
-                            for _ in 0..rep { inputs.push(rate); }
-                        }
-                        inputs.push(belt.parse::<f32>().unwrap_or(0.0));
+                    while !opts.is_empty() && opts.last().unwrap_or(&"").find("-").is_none() {
+                        push_belts(opts.pop().unwrap_or(""), &mut inputs, &mut input_labels, &mut input_items);
                     }
                 }
                 "-out" => {
                     opts.pop();
-                    while opts.last().unwrap_or(&"").find("-").is_none() {
-                        let belt = opts.pop().unwrap_or("").to_lowercase();
-                        let compound = belt.split("x").collect::<Vec<&str>>();
-                        if compound.len() == 2 {
-                            let rate = compound[0].parse::<f32>().unwrap_or(0.0);
-                            let rep = compound[1].parse::<usize>().unwrap_or(0);
-                            for _ in 0..rep { outputs.push(rate); }
+                    while !opts.is_empty() && opts.last().unwrap_or(&"").find("-").is_none() {
+                        // `power=120!1` is fed before anything of a later priority
+                        let belt = opts.pop().unwrap_or("");
+                        let (belt, priority) = match belt.split_once("!") {
+                            Some((belt, priority)) => match priority.parse::<u32>() {
+                                Ok(priority) => (belt, Some(priority)),
+                                Err(_) => {
+                                    println!("Invalid priority: {}, expected a number like 120!1", priority);
+                                    (belt, None)
+                                }
+                            },
+                            None => (belt, None),
+                        };
+                        push_belts(belt, &mut outputs, &mut output_labels, &mut output_items);
+                        priorities.resize(outputs.len(), priority);
+                    }
+                },
+                "-miners" => {
+                    opts.pop();
+                    while !opts.is_empty() && opts.last().unwrap_or(&"").find("-").is_none() {
+                        let miner = opts.pop().unwrap_or("");
+                        match Miner::parse(&miner.to_lowercase()) {
+                            Some(miner) => miners.push(miner),
+                            None => println!("Invalid miner: {}, expected like 2xmk2@pure or mk3@normal:250%", miner),
+                        }
+                    }
+                },
+                "-consumers" => {
+                    opts.pop();
+                    while !opts.is_empty() && opts.last().unwrap_or(&"").find("-").is_none() {
+                        let consumer = opts.pop().unwrap_or("");
+                        match Consumer::parse(&consumer.to_lowercase()) {
+                            Some(consumer) => consumers.push(consumer),
+                            None => println!("Invalid consumer: {}, expected like 3xconstructor@30 or 2xsmelter@30:150%", consumer),
                         }
-                        outputs.push(belt.parse::<f32>().unwrap_or(0.0));
                     }
                 },
                 "-mb" => {
                     opts.pop();
-                    max_belt = opts
+                    max_belt = belt_speed(opts.pop().unwrap_or("")).unwrap_or(1200);
+                },
+                "-t" => {
+                    opts.pop();
+                    threads = opts
+                        .pop()
+                        .unwrap_or("")
+                        .parse::<usize>()
+                        .unwrap_or(1)
+                        .max(1);
+                },
+                "-alg" => {
+                    opts.pop();
+                    algorithm = match opts.pop().unwrap_or("").to_lowercase().as_str() {
+                        "ida" | "ida*" => Algorithm::IdaStar,
+                        "astar" | "a*" => Algorithm::AStar,
+                        alg => {
+                            println!("Unknown algorithm: {}, using astar", alg);
+                            Algorithm::AStar
+                        }
+                    };
+                },
+                "-cost" => {
+                    opts.pop();
+                    cost_model = match opts.pop().unwrap_or("").to_lowercase().as_str() {
+                        "buildings" => CostModel::Buildings,
+                        "splitters" => CostModel::Splitters,
+                        "mergers" => CostModel::Mergers,
+                        model => {
+                            println!("Unknown cost model: {}, using buildings", model);
+                            CostModel::Buildings
+                        }
+                    };
+                },
+                "-tl" => {
+                    opts.pop();
+                    time_limit = opts
                         .pop()
                         .unwrap_or("")
                         .parse::<u64>()
-                        .unwrap_or(1200);
+                        .unwrap_or(30);
+                },
+                "-bench" => {
+                    opts.pop();
+                    bench = true;
+                },
+                "-sim" => {
+                    opts.pop();
+                    simulate = true;
+                },
+                "-layout" => {
+                    opts.pop();
+                    layout = true;
+                },
+                "-floor" => {
+                    opts.pop();
+                    // `12x10` foundations
+                    let size = opts.pop().unwrap_or("");
+                    match size.split_once("x").map(|(w, h)| (w.parse::<usize>(), h.parse::<usize>())) {
+                        Some((Ok(w), Ok(h))) if w > 0 && h > 0 => floor = Some((w, h)),
+                        _ => println!("Invalid floor size: {}, expected like 12x10", size),
+                    }
+                },
+                "-manifold" => {
+                    opts.pop();
+                    manifold = true;
+                },
+                "-strict" => {
+                    opts.pop();
+                    strict = true;
+                },
+                "-saturate" => {
+                    opts.pop();
+                    saturate = true;
+                },
+                "-fluid" => {
+                    opts.pop();
+                    fluid = true;
+                    // an optional pipe tier, mk2 unless told otherwise
+                    max_belt = match opts.last().map(|x| x.to_lowercase()).as_deref() {
+                        Some("mk1") => {
+                            opts.pop();
+                            PIPE_MK1
+                        }
+                        Some("mk2") => {
+                            opts.pop();
+                            PIPE_MK2
+                        }
+                        _ => PIPE_MK2,
+                    };
+                },
+                "-block" => {
+                    opts.pop();
+                    // `2` blocks output 2, `2@15` lets it take 15/min
+                    block = Some(pop_limits(&mut opts));
+                },
+                "-starve" => {
+                    opts.pop();
+                    // `2` stops input 2, `2@15` leaves it delivering 15/min
+                    starve = Some(pop_limits(&mut opts));
+                },
+                "-burst" => {
+                    opts.pop();
+                    while !opts.is_empty() && opts.last().unwrap_or(&"").find("-").is_none() {
+                        // `1@480/2400` unloads input 1 at up to 480/min from 2400 items
+                        let source = opts.pop().unwrap_or("");
+                        let parsed = source
+                            .split_once("@")
+                            .and_then(|(input, rest)| Some((input, rest.split_once("/")?)))
+                            .map(|(input, (peak, buffer))| (input.parse::<usize>(), peak.parse::<f32>(), buffer.parse::<f32>()));
+                        match parsed {
+                            Some((Ok(input), Ok(peak), Ok(buffer))) if buffer > 0.0 => bursts.push((input, peak, buffer)),
+                            _ => println!("Invalid burst: {}, expected like 1@480/2400", source),
+                        }
+                    }
+                },
+                "-load" => {
+                    opts.pop();
+                    load = opts.pop().map(|x| x.to_string());
+                },
+                "-save" => {
+                    opts.pop();
+                    save = opts.pop().map(|x| x.to_string());
+                },
+                "-svg" => {
+                    opts.pop();
+                    svg = opts.pop().map(|x| x.to_string());
+                },
+                "-batch" => {
+                    opts.pop();
+                    batch = opts.pop().map(|x| x.to_string());
+                },
+                "-report" => {
+                    opts.pop();
+                    report = opts.pop().map(|x| x.to_string());
                 },
                 "-q" => {
                     opts.pop();
@@ -61,13 +324,57 @@ impl Args {
             }
         }
 
-        inputs.retain(|&x| x > 0.0);
-        outputs.retain(|&x| x > 0.0);
+        drop_repeated_labels(&mut input_labels, &mut output_labels);
+        // strict, sorting and priority designs pass on every input item, so
+        // there is no surplus for saturated merges to throttle away
+        let items = input_items.iter().chain(&output_items).any(|mix| !mix.is_empty());
+        if saturate && (strict || items || priorities.iter().any(|p| p.is_some())) {
+            println!("-saturate can't be used with -strict, items or priorities, ignoring it");
+            saturate = false;
+        }
+        inputs.extend(miner_inputs(&miners, max_belt));
+        let mut surplus = 0.0;
+        if !consumers.is_empty() {
+            outputs.extend(consumer_outputs(&consumers));
+            surplus = machines::surplus(inputs.iter().sum(), outputs.iter().sum());
+            if surplus > 0.0 {
+                outputs.push(surplus);
+            }
+        }
 
         Self {
             inputs,
             outputs,
+            input_labels,
+            output_labels,
+            input_items,
+            output_items,
+            priorities,
+            miners,
+            consumers,
+            surplus,
             max_belt,
+            threads,
+            algorithm,
+            cost_model,
+            time_limit,
+            bench,
+            simulate,
+            manifold,
+            fluid,
+            strict,
+            saturate,
+            layout,
+            floor,
+            block,
+            starve,
+            bursts,
+            network,
+            load,
+            save,
+            svg,
+            batch,
+            report,
             quit,
         }
     }
@@ -75,15 +382,149 @@ impl Args {
 
 impl Debug for Args {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "Args [\n  inputs: {:?}\n  outputs: {:?}\n  max_belt: {}\n  quit: {}\n]",
+        write!(f, "Args [\n  inputs: {:?}\n  outputs: {:?}\n  input_labels: {:?}\n  output_labels: {:?}\n  input_items: {:?}\n  output_items: {:?}\n  priorities: {:?}\n  miners: {:?}\n  consumers: {:?}\n  surplus: {}\n  max_belt: {}\n  threads: {}\n  algorithm: {:?}\n  cost_model: {:?}\n  time_limit: {}\n  bench: {}\n  simulate: {}\n  manifold: {}\n  fluid: {}\n  strict: {}\n  saturate: {}\n  layout: {}\n  floor: {:?}\n  block: {:?}\n  starve: {:?}\n  bursts: {:?}\n  network: {:?}\n  load: {:?}\n  save: {:?}\n  svg: {:?}\n  batch: {:?}\n  report: {:?}\n  quit: {}\n]",
             self.inputs,
             self.outputs,
+            self.input_labels,
+            self.output_labels,
+            self.input_items,
+            self.output_items,
+            self.priorities,
+            self.miners,
+            self.consumers,
+            self.surplus,
             self.max_belt,
+            self.threads,
+            self.algorithm,
+            self.cost_model,
+            self.time_limit,
+            self.bench,
+            self.simulate,
+            self.manifold,
+            self.fluid,
+            self.strict,
+            self.saturate,
+            self.layout,
+            self.floor,
+            self.block,
+            self.starve,
+            self.bursts,
+            self.network,
+            self.load,
+            self.save,
+            self.svg,
+            self.batch,
+            self.report,
             self.quit
         )
     }
 }
 
+// adds the belts of one -in or -out entry: a rate like 60, a repeated rate
+// like 60x3 or the items on a mixed belt like iron:60+copper:30, any of them
+// with a label in front as in copper_a=60. repeated belts number their
+// labels, copper_a_1, copper_a_2...
+fn push_belts(belt: &str, rates: &mut Vec<f32>, labels: &mut Vec<Option<String>>, items: &mut Vec<Vec<(String, f32)>>) {
+    let (label, belt) = match belt.split_once("=") {
+        Some((label, belt)) if is_label(label) => (Some(label.to_string()), belt.to_lowercase()),
+        Some((label, belt)) => {
+            println!("Invalid label: {}, labels start with a letter and can't look like in1 or out1", label);
+            (None, belt.to_lowercase())
+        }
+        None => (None, belt.to_lowercase()),
+    };
+
+    let mut mix = vec![];
+    let (rate, rep) = if belt.contains(":") {
+        for part in belt.split("+") {
+            match part.split_once(":").map(|(item, rate)| (item, rate.parse::<f32>())) {
+                Some((item, Ok(rate))) if !item.is_empty() && rate > 0.0 => mix.push((item.to_string(), rate)),
+                _ => {
+                    println!("Invalid item rate: {}, expected like iron:60", part);
+                    return;
+                }
+            }
+        }
+        (mix.iter().map(|(_, rate)| rate).sum(), 1)
+    } else {
+        match belt.split_once("x") {
+            Some((rate, rep)) => (rate.parse::<f32>().unwrap_or(0.0), rep.parse::<usize>().unwrap_or(0)),
+            None => (belt.parse::<f32>().unwrap_or(0.0), 1),
+        }
+    };
+    if rate <= 0.0 {
+        return;
+    }
+    for i in 0..rep {
+        rates.push(rate);
+        items.push(mix.clone());
+        labels.push(match &label {
+            Some(label) if rep > 1 => Some(format!("{}_{}", label, i + 1)),
+            label => label.clone(),
+        });
+    }
+}
+
+// a label names one belt, later uses of it are dropped
+fn drop_repeated_labels(input_labels: &mut [Option<String>], output_labels: &mut [Option<String>]) {
+    let mut seen = vec![];
+    for label in input_labels.iter_mut().chain(output_labels.iter_mut()) {
+        if let Some(name) = label {
+            if seen.contains(name) {
+                println!("Label used twice: {}", name);
+                *label = None;
+            } else {
+                seen.push(name.clone());
+            }
+        }
+    }
+}
+
+// belt tiers and the items/min each carries, slowest first
+pub const BELT_TIERS: [(&str, u64); 6] = [
+    ("mk1", 60),
+    ("mk2", 120),
+    ("mk3", 270),
+    ("mk4", 480),
+    ("mk5", 780),
+    ("mk6", 1200),
+];
+
+// `belt@rate` pairs like `2@15` up to the next flag, a bare `2` is at rate 0
+fn pop_limits(opts: &mut Vec<&str>) -> Vec<(usize, f32)> {
+    let mut limits = vec![];
+    while !opts.is_empty() && opts.last().unwrap_or(&"").find("-").is_none() {
+        let limit = opts.pop().unwrap_or("");
+        let (belt, rate) = limit.split_once("@").unwrap_or((limit, "0"));
+        if let (Ok(belt), Ok(rate)) = (belt.parse::<usize>(), rate.parse::<f32>()) {
+            limits.push((belt, rate));
+        }
+    }
+    limits
+}
+
+// the line before a -net flag and the network text after it. only a whole
+// -net word counts, so paths like my-network.svg stay with their flag
+pub fn split_network(line: &str) -> (&str, Option<&str>) {
+    let whole = |i: usize| {
+        line[..i].chars().next_back().is_none_or(char::is_whitespace) &&
+            line[i + 4..].chars().next().is_none_or(char::is_whitespace)
+    };
+    match line.match_indices("-net").map(|(i, _)| i).find(|&i| whole(i)) {
+        Some(i) => (&line[..i], Some(line[i + 4..].trim())),
+        None => (line, None),
+    }
+}
+
+// a belt limit in items/min, either a number or a tier name like mk5
+pub fn belt_speed(belt: &str) -> Option<u64> {
+    let belt = belt.to_lowercase();
+    match BELT_TIERS.iter().find(|(name, _)| *name == belt) {
+        Some(&(_, speed)) => Some(speed),
+        None => belt.parse::<u64>().ok(),
+    }
+}
+
 pub fn read_input() -> String {
     let mut buffer = String::new();
     let stdin = io::stdin().read_line(&mut buffer);
@@ -0,0 +1,208 @@
+use crate::cmd::read_input;
+use std::fs::File;
+use std::io;
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+// a small line editor for the session: left and right, home and end (also
+// ctrl-a and ctrl-e) move the cursor, backspace and delete remove a
+// character, up and down go through the lines entered before, ctrl-c drops
+// the line and ctrl-d on an empty line ends the input
+pub struct Editor {
+    history: Vec<String>,
+}
+
+// a key read from the terminal
+#[derive(Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Up,
+    Down,
+    Cancel,
+    Eof,
+    // the input itself ended
+    Closed,
+    Other,
+}
+
+// the terminal's settings, put back once a line has been read
+struct Raw(String);
+
+impl Raw {
+    // puts the terminal in non canonical mode without echo or signals, None
+    // if stty isn't there to do it
+    fn enable() -> Option<Raw> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Some(Raw(saved.trim().to_string()))
+    }
+}
+
+impl Drop for Raw {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.0]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty").ok()?)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// the next key from `bytes`, arrows and the like come as escape sequences
+fn read_key(bytes: &mut impl Iterator<Item = u8>) -> Key {
+    let Some(byte) = bytes.next() else {
+        return Key::Closed;
+    };
+    match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x05 => Key::End,
+        0x03 => Key::Cancel,
+        0x04 => Key::Eof,
+        0x1b => match (bytes.next(), bytes.next()) {
+            (Some(b'[' | b'O'), Some(b'A')) => Key::Up,
+            (Some(b'[' | b'O'), Some(b'B')) => Key::Down,
+            (Some(b'[' | b'O'), Some(b'C')) => Key::Right,
+            (Some(b'[' | b'O'), Some(b'D')) => Key::Left,
+            (Some(b'[' | b'O'), Some(b'H')) => Key::Home,
+            (Some(b'[' | b'O'), Some(b'F')) => Key::End,
+            (Some(b'['), Some(b'3')) => match bytes.next() {
+                Some(b'~') => Key::Delete,
+                _ => Key::Other,
+            },
+            _ => Key::Other,
+        },
+        byte if byte < 0x20 => Key::Other,
+        byte => {
+            // the rest of a utf-8 character, by the count its first byte gives
+            let mut encoded = vec![byte];
+            let more = match byte {
+                0xc0..=0xdf => 1,
+                0xe0..=0xef => 2,
+                0xf0..=0xf7 => 3,
+                _ => 0,
+            };
+            encoded.extend(bytes.take(more));
+            match String::from_utf8(encoded).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+    }
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor { history: vec![] }
+    }
+
+    // the next line with its newline, or an empty string at the end of the
+    // input like `read_input`. input that isn't a terminal is read as is
+    pub fn read_line(&mut self) -> String {
+        if !io::stdin().is_terminal() {
+            return read_input();
+        }
+        let Some(_raw) = Raw::enable() else {
+            return read_input();
+        };
+        let stdin = io::stdin();
+        let mut bytes = stdin.lock().bytes().map_while(Result::ok);
+        self.edit(&mut bytes, &mut io::stdout())
+    }
+
+    // edits a line from the keys in `bytes`, redrawing it on `out`
+    fn edit(&mut self, bytes: &mut impl Iterator<Item = u8>, out: &mut impl Write) -> String {
+        let mut line: Vec<char> = vec![];
+        let mut cursor = 0;
+        // place in the history being shown, the line being typed is kept
+        // aside while going through it
+        let mut recalled = self.history.len();
+        let mut draft = vec![];
+
+        loop {
+            match read_key(bytes) {
+                Key::Char(c) => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => break,
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                Key::Delete if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Right => cursor = (cursor + 1).min(line.len()),
+                Key::Home => cursor = 0,
+                Key::End => cursor = line.len(),
+                Key::Up if recalled > 0 => {
+                    if recalled == self.history.len() {
+                        draft = line.clone();
+                    }
+                    recalled -= 1;
+                    line = self.history[recalled].chars().collect();
+                    cursor = line.len();
+                }
+                Key::Down if recalled < self.history.len() => {
+                    recalled += 1;
+                    line = match self.history.get(recalled) {
+                        Some(previous) => previous.chars().collect(),
+                        None => draft.clone(),
+                    };
+                    cursor = line.len();
+                }
+                Key::Cancel => {
+                    let _ = writeln!(out, "^C");
+                    line.clear();
+                    cursor = 0;
+                    recalled = self.history.len();
+                    continue;
+                }
+                Key::Eof | Key::Closed if line.is_empty() => {
+                    let _ = writeln!(out);
+                    return String::new();
+                }
+                Key::Closed => break,
+                _ => continue,
+            }
+            // redraw the whole line, then step back to the cursor
+            let text = line.iter().collect::<String>();
+            let _ = write!(out, "\r\x1b[K{}", text);
+            if cursor < line.len() {
+                let _ = write!(out, "\x1b[{}D", line.len() - cursor);
+            }
+            let _ = out.flush();
+        }
+
+        let _ = writeln!(out);
+        let text = line.iter().collect::<String>();
+        if !text.trim().is_empty() && self.history.last() != Some(&text) {
+            self.history.push(text.clone());
+        }
+        text + "\n"
+    }
+}
+
+#[cfg(test)]
+impl Editor {
+    // the line typed as `keys`, without a terminal
+    pub fn edit_keys(&mut self, keys: &[u8]) -> String {
+        self.edit(&mut keys.iter().copied(), &mut vec![])
+    }
+}
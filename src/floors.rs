@@ -0,0 +1,170 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{BalancerGraph, Edge, Node, NodeKind};
+use crate::layout::{columns, place, GridLayout, ATTEMPTS, FOUNDATION, MARGIN};
+use std::fmt;
+use std::fmt::Formatter;
+
+// height from one floor to the next in metres, a foundation and two walls
+pub const FLOOR_HEIGHT: usize = 9;
+// the tallest a conveyor lift can be built in one piece
+pub const MAX_LIFT: usize = 48;
+
+// a conveyor lift carrying a belt up from one floor to another
+pub struct Lift {
+    pub name: String,
+    // floors counted from 0 at the bottom
+    pub from: usize,
+    pub to: usize,
+    // the foundation it stands on, the same on every floor it passes
+    pub at: (usize, usize),
+}
+
+impl Lift {
+    pub fn height(&self) -> usize {
+        (self.to - self.from) * FLOOR_HEIGHT
+    }
+}
+
+pub struct FloorPlan {
+    // bottom floor first, all cut to the same footprint
+    pub floors: Vec<GridLayout>,
+    pub lifts: Vec<Lift>,
+}
+
+impl FloorPlan {
+    pub fn width(&self) -> usize {
+        self.floors.first().map_or(0, |floor| floor.width())
+    }
+
+    pub fn height(&self) -> usize {
+        self.floors.first().map_or(0, |floor| floor.height())
+    }
+
+    pub fn belt_length(&self) -> usize {
+        self.floors.iter().map(|floor| floor.belt_length()).sum()
+    }
+
+    pub fn lift_height(&self) -> usize {
+        self.lifts.iter().map(|lift| lift.height()).sum()
+    }
+}
+
+// the stages `per_floor` at a time on floors stacked bottom up, belts going
+// up a floor or more run through lifts standing in a column at the east
+// edge of every floor. None if a floor can't be routed
+fn stack(graph: &BalancerGraph, columns: &[usize], per_floor: usize, gap: usize, spacing: usize) -> Option<FloorPlan> {
+    let floor_of = |n: usize| columns[n] / per_floor;
+    let count = (0..graph.nodes.len()).map(floor_of).max().unwrap_or(0) + 1;
+    let riser = MARGIN + per_floor * (gap + 1);
+
+    // one lift for every belt between floors. lifts share a foundation when
+    // they are on no floor together, else each takes the next free one
+    let mut lifts: Vec<(usize, Lift)> = vec![];
+    for (e, edge) in graph.edges.iter().enumerate().filter(|(_, edge)| floor_of(edge.from) != floor_of(edge.to)) {
+        let (from, to) = (floor_of(edge.from), floor_of(edge.to));
+        let slot = (0..)
+            .find(|&slot| lifts.iter().all(|(_, lift)| lift.at.1 != MARGIN + slot * 2 || lift.to < from || to < lift.from))
+            .unwrap_or(0);
+        let name = format!("lift{}", lifts.len() + 1);
+        lifts.push((e, Lift { name, from, to, at: (riser, MARGIN + slot * 2) }));
+    }
+
+    let mut floors = vec![];
+    for floor in 0..count {
+        let mut sub = BalancerGraph::default();
+        let mut local = vec![None; graph.nodes.len()];
+        let mut sub_columns = vec![];
+        for (n, node) in graph.nodes.iter().enumerate().filter(|&(n, _)| floor_of(n) == floor) {
+            local[n] = Some(sub.nodes.len());
+            sub.nodes.push(node.clone());
+            sub_columns.push(columns[n] - floor * per_floor);
+        }
+
+        // lifts stopping on or passing through this floor, then the belts in
+        // their first order so programmable splitters keep their sides
+        let mut fixed = vec![];
+        let mut ends = vec![None; graph.edges.len()];
+        for (i, (e, lift)) in lifts.iter().enumerate().filter(|(_, (_, lift))| (lift.from..=lift.to).contains(&floor)) {
+            let kind = if lift.from == floor { NodeKind::Output } else { NodeKind::Input };
+            fixed.push((sub.nodes.len(), lift.at));
+            ends[*e] = Some(sub.nodes.len());
+            sub.nodes.push(Node { kind, index: i + 1, label: Some(lift.name.clone()) });
+            sub_columns.push(per_floor);
+        }
+        for (e, edge) in graph.edges.iter().enumerate() {
+            let from = local[edge.from].or(ends[e].filter(|_| floor_of(edge.to) == floor));
+            let to = local[edge.to].or(ends[e].filter(|_| floor_of(edge.from) == floor));
+            if let (Some(from), Some(to)) = (from, to) {
+                sub.edges.push(Edge { from, to, rate: edge.rate });
+            }
+        }
+
+        floors.push(place(&sub, &sub_columns, &fixed, gap, spacing)?);
+    }
+
+    // every floor cut to the foundations any of them uses, so they line up
+    let corners = floors.iter().filter_map(|floor| floor.bounds()).collect::<Vec<_>>();
+    let corner = (corners.iter().map(|c| c.0.0).min()?, corners.iter().map(|c| c.0.1).min()?);
+    let end = (corners.iter().map(|c| c.1.0).max()?, corners.iter().map(|c| c.1.1).max()?);
+    for floor in &mut floors {
+        floor.crop(corner, end);
+    }
+    let lifts = lifts
+        .into_iter()
+        .map(|(_, lift)| Lift { at: (lift.at.0 - corner.0, lift.at.1 - corner.1), ..lift })
+        .collect();
+
+    Some(FloorPlan { floors, lifts })
+}
+
+impl BalancerGraph {
+    // the layout stacked over as few floors as keep every floor within
+    // `max` foundations, as (width, height). stages go on floors in order,
+    // so a balancer whose stages don't fit side by side climbs instead.
+    // None if even a stage a floor doesn't fit
+    pub fn floor_plan(&self, max: (usize, usize)) -> Option<FloorPlan> {
+        let columns = columns(self)?;
+        let stages = columns.iter().copied().max().unwrap_or(0) + 1;
+        (1..=stages)
+            .rev()
+            .filter_map(|per_floor| (0..ATTEMPTS).find_map(|i| stack(self, &columns, per_floor, 2 + i, 3 + i)))
+            .find(|plan| plan.width() <= max.0 && plan.height() <= max.1)
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    // the graph comes from the path the search reconstructed, laid out a
+    // stage at a time from the inputs
+    pub fn floor_plan(&self, result: &BalancerResult, max: (usize, usize)) -> Option<FloorPlan> {
+        self.graph(result)?.floor_plan(max)
+    }
+}
+
+impl fmt::Display for FloorPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} floors of {} x {} foundations ({} x {} m), {} m apart:",
+            self.floors.len(), self.width(), self.height(), self.width() * FOUNDATION, self.height() * FOUNDATION, FLOOR_HEIGHT
+        )?;
+        for (i, floor) in self.floors.iter().enumerate().rev() {
+            writeln!(f, "floor {}:", i + 1)?;
+            floor.write_grid(f)?;
+        }
+        for lift in &self.lifts {
+            write!(f, "  {}: floor {} -> {} at ({}, {}), {} m",
+                lift.name, lift.from + 1, lift.to + 1, lift.at.0, lift.at.1, lift.height()
+            )?;
+            if lift.height() > MAX_LIFT {
+                write!(f, ", over {} m so it takes {} lifts stacked", MAX_LIFT, lift.height().div_ceil(MAX_LIFT))?;
+            }
+            writeln!(f)?;
+        }
+
+        let crossings = self.floors.iter().map(|floor| floor.crossings()).sum::<usize>();
+        write!(f, "{} lifts climbing {} m, about {} m of belt", self.lifts.len(), self.lift_height(), self.belt_length())?;
+        match crossings {
+            0 => Ok(()),
+            n => write!(f, ", {} crossings where a belt goes over another on poles", n),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::graph::{BalancerGraph, NodeKind};
+use crate::rational::Rational;
+use std::fmt;
+use std::fmt::Formatter;
+
+pub struct FlowSolution {
+    // exact steady state rate of every edge, in items/min
+    pub rates: Vec<Rational>,
+    // edges carrying more than the belt limit
+    pub over: Vec<usize>,
+    names: Vec<(String, String)>,
+    max_belt: Rational,
+}
+
+// buildings follow the same rules as `Operation`, a splitter divides its one
+// input evenly over 2 or 3 outputs and a merger sums 2 or 3 inputs onto one.
+// a smart splitter with an overflow belt passes on what its other belts draw
+fn check_building(graph: &BalancerGraph, node: usize) -> Result<(), String> {
+    let ins = graph.incoming(node).len();
+    let outs = graph.outgoing(node).len();
+    let name = graph.nodes[node].name();
+
+    match graph.nodes[node].kind {
+        NodeKind::Input if ins != 0 || outs != 1 => Err(format!("{} needs exactly one outgoing belt", name)),
+        NodeKind::Output if outs != 0 || ins != 1 => Err(format!("{} needs exactly one incoming belt", name)),
+        NodeKind::Splitter if ins != 1 || !(1..=3).contains(&outs) => {
+            Err(format!("{} has {} inputs and {} outputs, a splitter takes 1 and gives 1 to 3", name, ins, outs))
+        }
+        NodeKind::Merger if outs != 1 || !(1..=3).contains(&ins) => {
+            Err(format!("{} has {} inputs and {} outputs, a merger takes 1 to 3 and gives 1", name, ins, outs))
+        }
+        NodeKind::Filter if ins != 1 || !(1..=3).contains(&outs) => {
+            Err(format!("{} has {} inputs and {} outputs, a smart splitter takes 1 and gives 1 to 3", name, ins, outs))
+        }
+        // an F without an overflow belt is a programmable splitter sorting items by name
+        NodeKind::Filter if !graph.outgoing(node).iter().any(|e| graph.overflow.contains(e)) => {
+            Err(format!("{} is a programmable splitter sorting items, only belts of one item can be solved", name))
+        }
+        _ => Ok(()),
+    }
+}
+
+// solves the steady state rate of every belt in a network, loops included.
+// each belt out of a node carries that node's total input divided by its
+// number of outgoing belts, which gives one linear equation per belt.
+// `supply` is the rate of each input node (scaled by 1e8), in input order
+pub fn solve_flow(graph: &BalancerGraph, supply: &[u64], max_belt: u64) -> Result<FlowSolution, String> {
+    for node in 0..graph.nodes.len() {
+        check_building(graph, node)?;
+    }
+    let inputs = graph.nodes_of(NodeKind::Input);
+    if inputs.len() != supply.len() {
+        return Err(format!("{} input rates given for {} inputs", supply.len(), inputs.len()));
+    }
+
+    // x_e - (1 / outdeg(u)) * sum(x_f for f into u) = supply(u) / outdeg(u)
+    let n = graph.edges.len();
+    let mut matrix = vec![vec![Rational::zero(); n + 1]; n];
+    for (e, edge) in graph.edges.iter().enumerate() {
+        matrix[e][e] = Rational::new(1, 1);
+        if graph.nodes[edge.from].kind == NodeKind::Filter {
+            smart_splitter_row(graph, e, &mut matrix[e]);
+            continue;
+        }
+        let share = Rational::new(1, graph.outgoing(edge.from).len() as i128);
+        for f in graph.incoming(edge.from) {
+            matrix[e][f] = matrix[e][f] - share;
+        }
+        if let Some(i) = inputs.iter().position(|&x| x == edge.from) {
+            matrix[e][n] = Rational::from_scaled(supply[i]) * share;
+        }
+    }
+
+    let rates = gauss(matrix).ok_or("network has a loop that items can never leave")?;
+    if let Some(e) = (0..n).find(|&e| rates[e] < Rational::zero()) {
+        let from = graph.nodes[graph.edges[e].from].name();
+        return Err(format!("{} gets less than the belts out of it draw, {} short", from, -rates[e]));
+    }
+    let max_belt = Rational::from_scaled(max_belt);
+    let over = (0..n).filter(|&e| rates[e] > max_belt).collect();
+    let names = graph.edges
+        .iter()
+        .map(|e| (graph.nodes[e.from].name(), graph.nodes[e.to].name()))
+        .collect();
+
+    Ok(FlowSolution {
+        rates,
+        over,
+        names,
+        max_belt,
+    })
+}
+
+// a belt out of a smart splitter carries the rate written on it, what the
+// output behind it draws. the rest of the input goes evenly to its belts
+// without a rate, or to its overflow belts when every other belt has one
+fn smart_splitter_row(graph: &BalancerGraph, e: usize, row: &mut [Rational]) {
+    let n = graph.edges.len();
+    let from = graph.edges[e].from;
+    let outs = graph.outgoing(from);
+    let drawn = |f: &usize| !graph.overflow.contains(f) && graph.edges[*f].rate > 0;
+    let open = outs.iter().copied().filter(|f| !graph.overflow.contains(f) && !drawn(f)).collect::<Vec<usize>>();
+    let rest = match open.is_empty() {
+        true => outs.iter().copied().filter(|f| graph.overflow.contains(f)).collect(),
+        false => open,
+    };
+
+    if drawn(&e) {
+        row[n] = Rational::from_scaled(graph.edges[e].rate);
+    } else if rest.contains(&e) {
+        let share = Rational::new(1, rest.len() as i128);
+        for f in graph.incoming(from) {
+            row[f] = row[f] - share;
+        }
+        let taken = outs.iter().filter(|f| drawn(f)).map(|&f| Rational::from_scaled(graph.edges[f].rate));
+        row[n] = -taken.fold(Rational::zero(), |a, b| a + b) * share;
+    }
+}
+
+// gauss-jordan elimination on an augmented matrix, None if it is singular
+fn gauss(mut m: Vec<Vec<Rational>>) -> Option<Vec<Rational>> {
+    let n = m.len();
+    for col in 0..n {
+        let pivot = (col..n).find(|&r| !m[r][col].is_zero())?;
+        m.swap(col, pivot);
+
+        let p = m[col][col];
+        for x in m[col].iter_mut() {
+            *x = *x / p;
+        }
+        let pivot_row = m[col].clone();
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != col && !row[col].is_zero() {
+                let factor = row[col];
+                for (x, &p) in row.iter_mut().zip(&pivot_row).skip(col) {
+                    *x = *x - p * factor;
+                }
+            }
+        }
+    }
+
+    Some(m.into_iter().map(|row| row[n]).collect())
+}
+
+impl fmt::Display for FlowSolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (e, ((from, to), rate)) in self.names.iter().zip(&self.rates).enumerate() {
+            if e != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} -> {}: {}", from, to, rate)?;
+            if self.over.contains(&e) {
+                write!(f, " over the {} belt limit", self.max_belt)?;
+            }
+        }
+        Ok(())
+    }
+}
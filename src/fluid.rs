@@ -0,0 +1,83 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::helpers::{distinct, multiset};
+use crate::operation::Operation;
+use std::fmt;
+use std::fmt::Formatter;
+
+// m³/min a pipeline carries
+pub const PIPE_MK1: u64 = 300;
+pub const PIPE_MK2: u64 = 600;
+
+// pipeline junctions join up to 4 pipes, so the same 1 to 2 or 3 and 2 or 3
+// to 1 shapes as splitters and mergers. unlike a splitter a junction does not
+// hand out equal shares, fluid goes where it is drawn, so a junction can split
+// unevenly as long as whatever is downstream of each branch pulls its part.
+// trying every uneven split would swamp the search, so all parts but the last
+// have to be rates one of the outputs asks for
+pub fn fluid_splits<'a>(state: &'a [u64], outputs: &[u64]) -> impl Iterator<Item = Operation> + 'a {
+    let targets = multiset(outputs.to_vec()).to_vec();
+    let targets = distinct(&targets).collect::<Vec<u64>>();
+
+    distinct(state).flat_map(move |n| {
+        let mut ops = vec![];
+        for (i, &a) in targets.iter().enumerate() {
+            if a < n && a != n - a {
+                ops.push(Operation::Split {
+                    input: n,
+                    output: (Some(a), Some(n - a), None),
+                });
+            }
+            for &b in &targets[i..] {
+                if a + b < n && !(a == b && b == n - a - b) {
+                    ops.push(Operation::Split {
+                        input: n,
+                        output: (Some(a), Some(b), Some(n - a - b)),
+                    });
+                }
+            }
+        }
+        ops
+    })
+}
+
+pub struct FluidReport {
+    // junction number and the uneven split it makes
+    pub uneven: Vec<(usize, Operation)>,
+    pub pipe: u64,
+}
+
+// balancer related functions
+impl Balancer {
+    // where the plan leans on fluid going where it is drawn
+    pub fn fluid_report(&self, result: &BalancerResult) -> FluidReport {
+        let uneven = result
+            .iter()
+            .enumerate()
+            .filter(|(_, (op, _))| match op {
+                Operation::Split { output, .. } => {
+                    let parts = [output.0, output.1, output.2];
+                    parts.iter().flatten().any(|&x| Some(x) != output.0)
+                }
+                _ => false,
+            })
+            .map(|(i, (op, _))| (i + 1, op.clone()))
+            .collect();
+
+        FluidReport {
+            uneven,
+            pipe: self.max_belt,
+        }
+    }
+}
+
+impl fmt::Display for FluidReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "fluid mode, pipes carry up to {} m³/min and each step is a pipeline junction", self.pipe as f64 / 1e8)?;
+        for (step, op) in &self.uneven {
+            writeln!(f, "  step {} ({}) only divides like this while each branch draws its share", step, op)?;
+        }
+        write!(f, "  junctions never split evenly on their own, under backpressure the fluid settles \
+            by pressure and pipe layout rather than in fixed shares, so limit flow at the consumers \
+            or with valves")
+    }
+}
@@ -0,0 +1,291 @@
+use crate::balancer::{Balancer, BalancerResult, BalancerState};
+use crate::helpers::{apply, multiset};
+use crate::operation::Operation;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Input,
+    Output,
+    Splitter,
+    Merger,
+    // programmable splitter sorting items by type
+    Filter,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    // 1 based, counted separately for each kind
+    pub index: usize,
+    // name given to an input or output, like copper_a
+    pub label: Option<String>,
+}
+
+impl Node {
+    // the label if there is one, else a name like in1 or S2
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => self.plain_name(),
+        }
+    }
+
+    // how the network format writes the node, in1:copper_a when it has a label
+    pub fn written_name(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{}:{}", self.plain_name(), label),
+            None => self.plain_name(),
+        }
+    }
+
+    fn plain_name(&self) -> String {
+        match self.kind {
+            NodeKind::Input => format!("in{}", self.index),
+            NodeKind::Output => format!("out{}", self.index),
+            NodeKind::Splitter => format!("S{}", self.index),
+            NodeKind::Merger => format!("M{}", self.index),
+            NodeKind::Filter => format!("F{}", self.index),
+        }
+    }
+}
+
+// a belt between two buildings, carrying `rate` items/min (scaled by 1e8)
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub rate: u64,
+}
+
+// the buildings and belts of a balancer. graphs built from a solved path store
+// their nodes in an order where every belt goes from an earlier node to a later
+// one, hand built networks may contain loops
+#[derive(Clone, Debug, Default)]
+pub struct BalancerGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    // belts out of smart splitters that only take what the other outputs can't
+    pub overflow: Vec<usize>,
+}
+
+impl BalancerGraph {
+    // lay out the belts of a path, returns None if an operation
+    // uses a rate that no open belt carries
+    pub fn new(inputs: &[u64], outputs: &[u64], path: &[(Operation, BalancerState)]) -> Option<BalancerGraph> {
+        let mut graph = BalancerGraph::default();
+        let open = inputs
+            .iter()
+            .map(|&rate| (graph.add_node(NodeKind::Input), rate))
+            .collect();
+        graph.lay_out(open, outputs, path)?;
+
+        Some(graph)
+    }
+
+    // adds the buildings of a path onto belts that are already open, as
+    // (from node, rate), and ends every belt left in an output
+    pub fn lay_out(&mut self, open: Vec<(usize, u64)>, outputs: &[u64], path: &[(Operation, BalancerState)]) -> Option<()> {
+        let mut open = self.lay_out_path(open, path)?;
+        for &rate in outputs {
+            let node = self.add_node(NodeKind::Output);
+            let i = open.iter().position(|&(_, x)| x == rate)?;
+            let (from, _) = open.remove(i);
+            self.edges.push(Edge { from, to: node, rate });
+        }
+
+        open.is_empty().then_some(())
+    }
+
+    // adds the buildings of a path onto open belts, returns the belts left open
+    pub fn lay_out_path(&mut self, mut open: Vec<(usize, u64)>, path: &[(Operation, BalancerState)]) -> Option<Vec<(usize, u64)>> {
+        for (op, _) in path {
+            let kind = match op {
+                Operation::Split { .. } => NodeKind::Splitter,
+                Operation::Merge { .. } => NodeKind::Merger,
+                Operation::Filter { .. } => NodeKind::Filter,
+                Operation::Err => return None,
+            };
+            let node = self.add_node(kind);
+
+            for rate in op.inputs().into_iter().flatten() {
+                let i = open.iter().position(|&(_, x)| x == rate)?;
+                let (from, _) = open.remove(i);
+                self.edges.push(Edge { from, to: node, rate });
+            }
+            for rate in op.outputs().into_iter().flatten() {
+                open.push((node, rate));
+            }
+        }
+
+        Some(open)
+    }
+
+    pub fn add_node(&mut self, kind: NodeKind) -> usize {
+        let index = self.nodes.iter().filter(|n| n.kind == kind).map(|n| n.index).max().unwrap_or(0) + 1;
+        self.nodes.push(Node { kind, index, label: None });
+        self.nodes.len() - 1
+    }
+
+    // names the inputs or outputs in order, None keeps the plain name
+    pub fn label(&mut self, kind: NodeKind, labels: &[Option<String>]) {
+        for (node, label) in self.nodes_of(kind).into_iter().zip(labels) {
+            self.nodes[node].label = label.clone();
+        }
+    }
+
+    // the node with a name like in1, out2, S3, M4 or F5, added if it isn't in the graph yet
+    pub fn node_by_name(&mut self, name: &str) -> Option<usize> {
+        let split = name.find(|c: char| c.is_ascii_digit())?;
+        let kind = match &name[..split] {
+            "in" => NodeKind::Input,
+            "out" => NodeKind::Output,
+            "S" | "s" => NodeKind::Splitter,
+            "M" | "m" => NodeKind::Merger,
+            "F" | "f" => NodeKind::Filter,
+            _ => return None,
+        };
+        let index = name[split..].parse::<usize>().ok().filter(|&i| i > 0)?;
+
+        if let Some(i) = self.nodes.iter().position(|n| n.kind == kind && n.index == index) {
+            return Some(i);
+        }
+        self.nodes.push(Node { kind, index, label: None });
+        Some(self.nodes.len() - 1)
+    }
+
+    // adds a belt whose rate is not known yet
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.edges.push(Edge { from, to, rate: 0 });
+    }
+
+    // nodes of one kind, in order of their index
+    pub fn nodes_of(&self, kind: NodeKind) -> Vec<usize> {
+        let mut nodes = (0..self.nodes.len()).filter(|&i| self.nodes[i].kind == kind).collect::<Vec<usize>>();
+        nodes.sort_by_key(|&i| self.nodes[i].index);
+        nodes
+    }
+
+    // edges going into a node, in the order they were connected
+    pub fn incoming(&self, node: usize) -> Vec<usize> {
+        (0..self.edges.len()).filter(|&e| self.edges[e].to == node).collect()
+    }
+
+    pub fn outgoing(&self, node: usize) -> Vec<usize> {
+        (0..self.edges.len()).filter(|&e| self.edges[e].from == node).collect()
+    }
+}
+
+// path and graph being built together, the state follows every operation
+pub struct Builder {
+    pub graph: BalancerGraph,
+    pub path: Vec<(Operation, BalancerState)>,
+    pub state: Vec<u64>,
+}
+
+impl Builder {
+    pub fn new(inputs: &[u64]) -> Builder {
+        Builder {
+            graph: BalancerGraph::default(),
+            path: vec![],
+            state: multiset(inputs.to_vec()).to_vec(),
+        }
+    }
+
+    pub fn push(&mut self, op: Operation) {
+        let next = apply(&self.state, &op);
+        self.state = next.to_vec();
+        self.path.push((op, next));
+    }
+
+    pub fn split(&mut self, (from, rate): (usize, u64), ways: u64) -> Vec<(usize, u64)> {
+        let splitter = self.graph.add_node(NodeKind::Splitter);
+        self.graph.edges.push(Edge { from, to: splitter, rate });
+        let part = rate / ways;
+        let third = if ways == 3 { Some(part) } else { None };
+        self.push(Operation::Split { input: rate, output: (Some(part), Some(part), third) });
+        vec![(splitter, part); ways as usize]
+    }
+
+    pub fn merge(&mut self, belts: &[(usize, u64)]) -> (usize, u64) {
+        let merger = self.graph.add_node(NodeKind::Merger);
+        for &(from, rate) in belts {
+            self.graph.edges.push(Edge { from, to: merger, rate });
+        }
+        let rate = belts.iter().map(|(_, rate)| rate).sum();
+        let input = (Some(belts[0].1), Some(belts[1].1), belts.get(2).map(|b| b.1));
+        self.push(Operation::Merge { input, output: rate });
+        (merger, rate)
+    }
+}
+
+// prints the network text format read by `parse_network`, one line per
+// building with everything its belts lead to. belts of unknown rate leave
+// the rate out, overflow belts say so after the rate
+impl fmt::Display for BalancerGraph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for node in 0..self.nodes.len() {
+            let outgoing = self.outgoing(node);
+            if outgoing.is_empty() {
+                continue;
+            }
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+
+            let targets = outgoing
+                .iter()
+                .map(|&e| {
+                    let target = match self.edges[e].rate {
+                        0 => self.nodes[self.edges[e].to].written_name(),
+                        rate => format!("{} ({})", self.nodes[self.edges[e].to].written_name(), rate as f64 / 1e8),
+                    };
+                    match self.overflow.contains(&e) {
+                        true => format!("{} overflow", target),
+                        false => target,
+                    }
+                })
+                .collect::<Vec<String>>();
+            write!(f, "{} -> {}", self.nodes[node].written_name(), targets.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    pub fn graph(&self, result: &BalancerResult) -> Option<BalancerGraph> {
+        // strict results are laid out while they are built, their rates alone
+        // don't say which belts were mixed
+        let mut graph = match result.graph() {
+            Some(graph) => graph.clone(),
+            None => BalancerGraph::new(&self.inputs, &self.outputs, result.path())?,
+        };
+        // outputs are laid out in the order they were asked for, each taking the
+        // first open belt of its rate, so equal outputs keep their labels in order
+        graph.label(NodeKind::Input, &self.input_labels);
+        graph.label(NodeKind::Output, &self.output_labels);
+        Some(graph)
+    }
+
+    // what reports call each input, its label or in1, in2...
+    pub fn input_names(&self) -> Vec<String> {
+        terminal_names("in", &self.input_labels, self.inputs.len())
+    }
+
+    pub fn output_names(&self) -> Vec<String> {
+        terminal_names("out", &self.output_labels, self.outputs.len())
+    }
+}
+
+fn terminal_names(prefix: &str, labels: &[Option<String>], count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| match labels.get(i) {
+            Some(Some(label)) => label.clone(),
+            _ => format!("{}{}", prefix, i + 1),
+        })
+        .collect()
+}
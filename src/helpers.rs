@@ -1,35 +1,78 @@
 use std::collections::{HashMap, HashSet};
-use std::iter;
 use crate::balancer::BalancerState;
 use crate::operation::{Operation, ReverseOperation};
 
-pub fn combinations(state: &Vec<u64>, n: u32) -> Vec<Vec<u64>> {
-    if n == 0 || n > state.len() as u32 {
-        return vec![];
-    }
+// iterates the k-combinations (k = 2 or 3) of a sorted state by value, equal values
+// are stepped over so the same combination is never produced twice
+pub struct Combinations<'a> {
+    state: &'a [u64],
+    k: usize,
+    idx: [usize; 3],
+    started: bool,
+    done: bool,
+}
 
-    if n == state.len() as u32 {
-        return vec![state.to_vec()];
+impl<'a> Combinations<'a> {
+    pub fn new(state: &'a [u64], k: usize) -> Combinations<'a> {
+        Combinations {
+            state,
+            k,
+            idx: [0, 1, 2],
+            started: false,
+            done: !(2..=3).contains(&k) || k > state.len(),
+        }
     }
 
-    if n == 1 {
-        return state.iter().map(|x| vec![*x]).collect::<Vec<Vec<u64>>>();
-    }
+    // move the rightmost index that can be moved onto its next distinct value
+    // and pack every index after it directly behind it
+    fn advance(&mut self) -> bool {
+        let n = self.state.len();
+        let mut p = self.k;
+
+        while p > 0 {
+            p -= 1;
+            let mut i = self.idx[p] + 1;
+            while i < n && self.state[i] == self.state[i - 1] {
+                i += 1;
+            }
+            if i + (self.k - 1 - p) < n {
+                self.idx[p] = i;
+                for q in p + 1..self.k {
+                    self.idx[q] = self.idx[q - 1] + 1;
+                }
+                return true;
+            }
+        }
 
-    let mut res = vec![];
+        false
+    }
+}
 
-    for i in 0..=state.len() as u32 - n {
-        let current = state[i as usize];
-        let remaining = &state[i as usize + 1..].to_vec();
+impl Iterator for Combinations<'_> {
+    type Item = (Option<u64>, Option<u64>, Option<u64>);
 
-        for mut sub in combinations(remaining, n - 1) {
-            let mut new = vec![current];
-            new.append(&mut sub);
-            res.push(new);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
+        if self.started && !self.advance() {
+            self.done = true;
+            return None;
+        }
+        self.started = true;
+
+        let third = if self.k == 3 { Some(self.state[self.idx[2]]) } else { None };
+        Some((Some(self.state[self.idx[0]]), Some(self.state[self.idx[1]]), third))
     }
+}
 
-    res
+// distinct values of a sorted state
+pub fn distinct(state: &[u64]) -> impl Iterator<Item = u64> + '_ {
+    state
+        .iter()
+        .enumerate()
+        .filter(|&(i, val)| i == 0 || state[i - 1] != *val)
+        .map(|(_, val)| *val)
 }
 
 pub fn gcd(mut a: u64, mut b: u64) -> u64 {
@@ -96,94 +139,44 @@ pub fn validate_state(state: &BalancerState, gcd: u64) -> bool{
     true
 }
 
-pub fn splits(state: &Vec<u64>) -> Vec<(Operation, BalancerState)> {
-    fn get_split(n: u64) -> Vec<(Operation, Vec<u64>)> {
-        let mut res = vec![];
-
-        let half = n / 2u64;
-        let third = n / 3u64;
-
-        let half_split = iter::repeat(half).take(2).collect::<Vec<u64>>();
-        let third_split = iter::repeat(third).take(3).collect::<Vec<u64>>();
-
-        res.push((Operation::Split {
-            input: n,
-            output: (Some(half), Some(half), None)
-        }, half_split));
-
-        res.push((Operation::Split {
-            input: n,
-            output: (Some(third), Some(third), Some(third))
-        }, third_split));
-
-        res
-    }
-
-    let mut res = vec![];
-    let values = state
-        .iter()
-        .cloned()
-        .collect::<HashSet<u64>>()
-        .iter()
-        .cloned()
-        .collect::<Vec<u64>>();
-
-    for val in values {
-        for (op, split) in get_split(val) {
-            let mut new_state = state.clone().to_vec();
-            new_state.remove(new_state.iter().position(|&x| x == val).unwrap());
-            new_state.extend(split);
-            res.push((op, multiset(new_state)));
-        }
-    }
-
-    res
-}
-
-pub fn merges(state: &Vec<u64>) -> Vec<(Operation, BalancerState)> {
-    let mut result = vec![];
-    let mut seen = HashSet::new();
-
-    for k in 2..=3 {
-        for combination in combinations(&state, k) {
-            let combination = multiset(combination);
-            if seen.contains(&combination) {
-                continue;
-            }
-            seen.insert(combination.clone());
-            let merged: u64 = combination.iter().sum();
-            let mut remaining = state.clone();
-
-            for &val in combination.iter() {
-                let i = remaining.iter().position(|&x| x == val).unwrap();
-                remaining.remove(i);
+pub fn splits(state: &[u64]) -> impl Iterator<Item = Operation> + '_ {
+    distinct(state).flat_map(|n| {
+        // only exact splits, a truncated third would lose items on paper only
+        [2u64, 3].into_iter().filter(move |ways| n.is_multiple_of(*ways)).map(move |ways| {
+            let part = n / ways;
+            let third = if ways == 3 { Some(part) } else { None };
+            Operation::Split {
+                input: n,
+                output: (Some(part), Some(part), third),
             }
+        })
+    })
+}
 
-            remaining.push(merged);
+pub fn merges(state: &[u64]) -> impl Iterator<Item = Operation> + '_ {
+    (2..=3)
+        .flat_map(move |k| Combinations::new(state, k))
+        .map(|input| Operation::Merge {
+            input,
+            output: input.0.unwrap_or(0) + input.1.unwrap_or(0) + input.2.unwrap_or(0),
+        })
+}
 
-            let new_state = multiset(remaining);
+// the state left after performing an operation on a sorted state
+pub fn apply(state: &[u64], op: &Operation) -> BalancerState {
+    let mut consumed = op.inputs();
+    let mut values = Vec::with_capacity(state.len() + 2);
 
-            let operation = match combination.len() {
-                2 => {
-                    Operation::Merge {
-                        input: (Some(combination.values[0]), Some(combination.values[1]), None),
-                        output: merged
-                    }
-                }
-                3 => {
-                    Operation::Merge {
-                        input: (Some(combination.values[0]), Some(combination.values[1]), Some(combination.values[2])),
-                        output: merged
-                    }
-                },
-                // this case should never happen
-                _ => {Operation::Err},
-            };
-
-            result.push((operation, new_state));
+    for &val in state {
+        if let Some(slot) = consumed.iter_mut().find(|x| **x == Some(val)) {
+            *slot = None;
+            continue;
         }
+        values.push(val);
     }
-    result
+    values.extend(op.outputs().into_iter().flatten());
+
+    multiset(values)
 }
 
 pub fn rev_splits(state: &Vec<u64>, gcd: u64) -> Vec<(ReverseOperation, BalancerState)> {
@@ -193,8 +186,8 @@ pub fn rev_splits(state: &Vec<u64>, gcd: u64) -> Vec<(ReverseOperation, Balancer
         for x in 1..(n / gcd / 2 + 1) {
             res.push((ReverseOperation::Split {
                 input: n,
-                output: (Some(x * gcd), Some((n / gcd) * gcd), None)
-            }, vec![x * gcd, (n / gcd) * gcd]));
+                output: (Some(x * gcd), Some(n - x * gcd), None)
+            }, vec![x * gcd, n - x * gcd]));
         }
 
         res
@@ -0,0 +1,135 @@
+use crate::balancer::{Balancer, BalancerResult, BalancerState};
+use crate::helpers::multiset;
+use crate::operation::Operation;
+use std::collections::HashMap;
+use std::time::Instant;
+
+enum Bound {
+    Found,
+    // smallest estimate that went over the threshold, None if nothing did
+    Exceeded(Option<i64>),
+    TimedOut,
+}
+
+struct IdaSearch<'a> {
+    balancer: &'a Balancer,
+    target: BalancerState,
+    gcd: u64,
+    start: Instant,
+    path: Vec<(Operation, BalancerState)>,
+    on_path: Vec<BalancerState>,
+    total_states: u64,
+    checked_states: u64,
+}
+
+// balancer related functions
+impl Balancer {
+    // a lower bound on the number of buildings left, unlike `heuristic` this
+    // never overestimates so ida* is guaranteed to return an optimal path.
+    // one building creates at most 3 belts and changes the belt count by at most 2
+    pub fn admissible_heuristic(state: &BalancerState, target: &BalancerState) -> i64 {
+        let mut counts: HashMap<u64, i64> = HashMap::new();
+        for &val in state.iter() {
+            *counts.entry(val).or_insert(0) += 1;
+        }
+
+        let mut missing = 0i64;
+        for &val in target.iter() {
+            let count = counts.entry(val).or_insert(0);
+            if *count > 0 {
+                *count -= 1;
+            } else {
+                missing += 1;
+            }
+        }
+
+        let belt_diff = (state.len() as i64 - target.len() as i64).abs();
+        ((missing + 2) / 3).max((belt_diff + 1) / 2)
+    }
+
+    // iterative deepening a*, memory use only grows with the length of the
+    // path being explored, at the cost of re-expanding states every iteration
+    pub fn find_ideal_balance_ida(&self, start: Instant) -> BalancerResult {
+        let initial_state = multiset(self.inputs.clone());
+        let target_state = multiset(self.outputs.clone());
+
+        let mut search = IdaSearch {
+            balancer: self,
+            gcd: self.search_gcd(),
+            target: target_state.clone(),
+            start,
+            path: vec![],
+            on_path: vec![initial_state.clone()],
+            total_states: 0,
+            checked_states: 0,
+        };
+
+        let mut threshold = Self::admissible_heuristic(&initial_state, &target_state);
+        loop {
+            match search.dfs(&initial_state, 0, threshold) {
+                Bound::Found => {
+                    return BalancerResult::new(
+                        search.path,
+                        search.total_states,
+                        search.checked_states,
+                        start.elapsed().as_secs_f64()
+                    );
+                }
+                Bound::Exceeded(Some(next)) => threshold = next,
+                Bound::Exceeded(None) => {
+                    println!("No Solution Exists :(");
+                    return BalancerResult::default();
+                }
+                Bound::TimedOut => {
+                    println!("No Solution Found after {}s :(", start.elapsed().as_secs());
+                    return BalancerResult::default();
+                }
+            }
+        }
+    }
+}
+
+impl IdaSearch<'_> {
+    fn dfs(&mut self, current: &BalancerState, cost: i64, threshold: i64) -> Bound {
+        let estimate = cost + Balancer::admissible_heuristic(current, &self.target);
+        if estimate > threshold {
+            return Bound::Exceeded(Some(estimate));
+        }
+        if *current == self.target {
+            return Bound::Found;
+        }
+        if self.start.elapsed().as_secs() >= self.balancer.time_limit {
+            return Bound::TimedOut;
+        }
+        self.checked_states += 1;
+
+        let mut next_states = self.balancer.get_states_fwd(&current.values, self.gcd).collect::<Vec<_>>();
+        self.total_states += next_states.len() as u64;
+        next_states.sort_by_cached_key(|(_, next)| Balancer::admissible_heuristic(next, &self.target));
+
+        let mut smallest = None;
+        for (action, next) in next_states {
+            if self.on_path.contains(&next) {
+                continue;
+            }
+
+            let new_cost = cost + action.cost(self.balancer.cost_model);
+            self.path.push((action, next.clone()));
+            self.on_path.push(next.clone());
+
+            match self.dfs(&next, new_cost, threshold) {
+                Bound::Found => return Bound::Found,
+                Bound::TimedOut => return Bound::TimedOut,
+                Bound::Exceeded(Some(f)) => {
+                    smallest = Some(smallest.map_or(f, |s: i64| s.min(f)));
+                }
+                Bound::Exceeded(None) => {}
+            }
+
+            self.path.pop();
+            self.on_path.pop();
+        }
+
+        Bound::Exceeded(smallest)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::balancer::BalancerState;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+pub type StateId = u32;
+
+const INLINE_LEN: usize = 11;
+// end of a chain of states sharing a hash
+const NONE: StateId = StateId::MAX;
+
+// every value in a valid state is a multiple of the gcd, so most states
+// fit in a handful of u16s once divided by it
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Packed {
+    Inline(u8, [u16; INLINE_LEN]),
+    Boxed(Box<[u64]>),
+}
+
+// maps each visited multiset to a small integer id so the search
+// can store ids instead of cloning states around. each state is only
+// kept in `states`, the lookup goes by its hash to the last id with
+// that hash and on through the ones before it
+pub struct StateArena {
+    gcd: u64,
+    hasher: RandomState,
+    ids: HashMap<u64, StateId>,
+    // the previous id with the same hash as each state, NONE if there isn't one
+    chains: Vec<StateId>,
+    states: Vec<Packed>,
+}
+
+impl StateArena {
+    pub fn new(gcd: u64) -> StateArena {
+        StateArena {
+            gcd: gcd.max(1),
+            hasher: RandomState::new(),
+            ids: HashMap::new(),
+            chains: vec![],
+            states: vec![],
+        }
+    }
+
+    fn pack(&self, state: &BalancerState) -> Packed {
+        let fits = state.len() <= INLINE_LEN &&
+            state.iter().all(|&x| x % self.gcd == 0 && x / self.gcd <= u16::MAX as u64);
+        if !fits {
+            return Packed::Boxed(state.values.clone());
+        }
+
+        let mut values = [0u16; INLINE_LEN];
+        for (i, &val) in state.iter().enumerate() {
+            values[i] = (val / self.gcd) as u16;
+        }
+        Packed::Inline(state.len() as u8, values)
+    }
+
+    pub fn intern(&mut self, state: &BalancerState) -> StateId {
+        let packed = self.pack(state);
+        let hash = self.hasher.hash_one(&packed);
+        let mut id = self.ids.get(&hash).copied().unwrap_or(NONE);
+        while id != NONE {
+            if self.states[id as usize] == packed {
+                return id;
+            }
+            id = self.chains[id as usize];
+        }
+
+        let id = self.states.len() as StateId;
+        self.states.push(packed);
+        self.chains.push(self.ids.insert(hash, id).unwrap_or(NONE));
+        id
+    }
+
+    pub fn to_vec(&self, id: StateId) -> Vec<u64> {
+        match &self.states[id as usize] {
+            Packed::Inline(len, values) => {
+                values[..*len as usize].iter().map(|&x| x as u64 * self.gcd).collect()
+            }
+            Packed::Boxed(values) => values.to_vec(),
+        }
+    }
+
+    pub fn get(&self, id: StateId) -> BalancerState {
+        BalancerState::new(self.to_vec(id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+}
@@ -0,0 +1,416 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{BalancerGraph, NodeKind};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::fmt::Formatter;
+
+// side length of a foundation in metres
+pub const FOUNDATION: usize = 8;
+// extra cost of taking a belt over another one on poles, so belts go round
+// when that is not much longer
+const CROSSING_COST: usize = 4;
+// extra cost of a bend, so belts run straight where they can
+const TURN_COST: usize = 1;
+// times the grid is spread out further before giving up
+pub const ATTEMPTS: usize = 8;
+// free foundations kept round the buildings while routing
+pub const MARGIN: usize = 2;
+
+// everything faces east, so a splitter's left is north and its right south
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Side {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Side {
+    fn step(self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        match self {
+            Side::North => Some((x, y.checked_sub(1)?)),
+            Side::East => Some((x + 1, y)),
+            Side::South => Some((x, y + 1)),
+            Side::West => Some((x.checked_sub(1)?, y)),
+        }
+    }
+}
+
+// how a route got to a cell heading some way, the cells it came over and the
+// way it was heading before
+type Step = (Vec<(usize, usize)>, Side);
+
+const SIDES: [Side; 4] = [Side::North, Side::East, Side::South, Side::West];
+
+// sides belts leave and enter a node by
+fn exits(kind: NodeKind) -> Vec<Side> {
+    match kind {
+        NodeKind::Merger => vec![Side::East],
+        NodeKind::Output => vec![],
+        // left, centre and right, in the order a programmable splitter lists them
+        _ => vec![Side::North, Side::East, Side::South],
+    }
+}
+
+fn entries(kind: NodeKind) -> Vec<Side> {
+    match kind {
+        NodeKind::Merger | NodeKind::Output => vec![Side::West, Side::North, Side::South],
+        NodeKind::Input => vec![],
+        _ => vec![Side::West],
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    // a building, by node
+    Node(usize),
+    // a belt running straight, across the grid or along it
+    Across,
+    Along,
+    Turn,
+    // two belts, one on poles over the other
+    Crossing,
+}
+
+pub struct GridLayout {
+    pub names: Vec<String>,
+    pub kinds: Vec<NodeKind>,
+    // nodes standing for conveyor lifts to other floors
+    pub lifts: Vec<usize>,
+    // foundation each building stands on, as (x, y) with y running south
+    pub positions: Vec<(usize, usize)>,
+    // foundations each belt runs over between its two buildings, by edge
+    pub routes: Vec<Vec<(usize, usize)>>,
+    pub grid: Vec<Vec<Cell>>,
+}
+
+impl GridLayout {
+    pub fn width(&self) -> usize {
+        self.grid.first().map_or(0, |row| row.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    // belts run from the centre of one building to the centre of the next
+    pub fn belt_length(&self) -> usize {
+        self.routes.iter().map(|route| (route.len() + 1) * FOUNDATION).sum()
+    }
+
+    pub fn crossings(&self) -> usize {
+        self.grid.iter().flatten().filter(|&&cell| cell == Cell::Crossing).count()
+    }
+
+    // corners of the foundations in use, as ((left, top), (right, bottom))
+    pub fn bounds(&self) -> Option<((usize, usize), (usize, usize))> {
+        let used = self.grid
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().filter(|(_, &c)| c != Cell::Empty).map(move |(x, _)| (x, y)))
+            .collect::<Vec<(usize, usize)>>();
+        let left = used.iter().map(|c| c.0).min()?;
+        let top = used.iter().map(|c| c.1).min()?;
+        let right = used.iter().map(|c| c.0).max()?;
+        let bottom = used.iter().map(|c| c.1).max()?;
+        Some(((left, top), (right, bottom)))
+    }
+
+    // cuts the grid down to the foundations between two corners
+    pub fn crop(&mut self, (left, top): (usize, usize), (right, bottom): (usize, usize)) {
+        let shift = |(x, y): (usize, usize)| (x - left, y - top);
+        self.grid = (top..=bottom)
+            .map(|y| (left..=right).map(|x| self.grid.get(y).and_then(|row| row.get(x)).copied().unwrap_or(Cell::Empty)).collect())
+            .collect();
+        self.positions = self.positions.iter().copied().map(shift).collect();
+        self.routes = self.routes.iter().map(|route| route.iter().copied().map(shift).collect()).collect();
+    }
+
+    // the map of the grid, then where each building stands
+    pub fn write_grid(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for row in &self.grid {
+            let line = row
+                .iter()
+                .map(|cell| match cell {
+                    Cell::Empty => '.',
+                    Cell::Node(n) if self.lifts.contains(n) => 'L',
+                    Cell::Node(n) => match self.kinds[*n] {
+                        NodeKind::Input => 'I',
+                        NodeKind::Output => 'O',
+                        NodeKind::Splitter => 'S',
+                        NodeKind::Merger => 'M',
+                        NodeKind::Filter => 'F',
+                    },
+                    Cell::Across => '-',
+                    Cell::Along => '|',
+                    Cell::Turn => '+',
+                    Cell::Crossing => 'x',
+                })
+                .collect::<String>();
+            writeln!(f, "  {}", line)?;
+        }
+        let places = self.names
+            .iter()
+            .zip(&self.positions)
+            .map(|(name, (x, y))| format!("{} ({}, {})", name, x, y))
+            .collect::<Vec<String>>();
+        writeln!(f, "  {}", places.join(", "))
+    }
+}
+
+// columns of the nodes, every belt leads to a later column with inputs in
+// the first and outputs in the last. None if the belts go round in a loop
+pub fn columns(graph: &BalancerGraph) -> Option<Vec<usize>> {
+    let mut column = vec![0; graph.nodes.len()];
+    let mut waiting = (0..graph.nodes.len()).map(|n| graph.incoming(n).len()).collect::<Vec<usize>>();
+    let mut ready = (0..graph.nodes.len()).filter(|&n| waiting[n] == 0).collect::<Vec<usize>>();
+    let mut placed = 0;
+    while let Some(node) = ready.pop() {
+        placed += 1;
+        for e in graph.outgoing(node) {
+            let to = graph.edges[e].to;
+            column[to] = column[to].max(column[node] + 1);
+            waiting[to] -= 1;
+            if waiting[to] == 0 {
+                ready.push(to);
+            }
+        }
+    }
+    if placed < graph.nodes.len() {
+        return None;
+    }
+
+    let last = column.iter().copied().max().unwrap_or(0);
+    for (n, node) in graph.nodes.iter().enumerate() {
+        match node.kind {
+            NodeKind::Input => column[n] = 0,
+            NodeKind::Output => column[n] = last.max(1),
+            _ => {}
+        }
+    }
+    Some(column)
+}
+
+struct Router<'a> {
+    graph: &'a BalancerGraph,
+    grid: Vec<Vec<Cell>>,
+    positions: Vec<(usize, usize)>,
+    lifts: Vec<usize>,
+}
+
+impl Router<'_> {
+    fn cell(&self, (x, y): (usize, usize)) -> Option<Cell> {
+        self.grid.get(y)?.get(x).copied()
+    }
+
+    // next to a building, such cells are kept for belts going into or out of it
+    fn beside_node(&self, at: (usize, usize)) -> bool {
+        SIDES.iter().any(|side| matches!(side.step(at).and_then(|c| self.cell(c)), Some(Cell::Node(_))))
+    }
+
+    // lifts stand at the east edge of a floor, belts reach them from the rest of it
+    fn exits(&self, node: usize) -> Vec<Side> {
+        match self.lifts.contains(&node) {
+            true => vec![Side::West, Side::North, Side::South],
+            false => exits(self.graph.nodes[node].kind),
+        }
+    }
+
+    fn entries(&self, node: usize) -> Vec<Side> {
+        match self.lifts.contains(&node) {
+            true => vec![Side::West, Side::North, Side::South],
+            false => entries(self.graph.nodes[node].kind),
+        }
+    }
+
+    // free cells next to a node on the given sides with the side each is on,
+    // a building's sides can only take one belt each
+    fn ports(&self, node: usize, sides: &[Side]) -> Vec<((usize, usize), Side)> {
+        sides
+            .iter()
+            .filter_map(|&side| Some((side.step(self.positions[node])?, side)))
+            .filter(|&(c, _)| self.cell(c) == Some(Cell::Empty))
+            .collect()
+    }
+
+    // cheapest route from one of `starts` to one of `ends`, each start with
+    // the way the belt heads off. belts can cross another belt running
+    // straight at right angles, going over it on poles
+    fn route(&self, starts: &[((usize, usize), Side)], ends: &[(usize, usize)]) -> Option<Vec<(usize, usize)>> {
+        let mut cost = HashMap::new();
+        let mut from: HashMap<((usize, usize), Side), Step> = HashMap::new();
+        let mut frontier = BinaryHeap::new();
+        for &start in starts {
+            cost.insert(start, 0);
+            frontier.push(Reverse((0, start)));
+        }
+
+        while let Some(Reverse((c, (at, heading)))) = frontier.pop() {
+            if cost.get(&(at, heading)).is_some_and(|&best| c > best) {
+                continue;
+            }
+            if ends.contains(&at) {
+                let mut route = vec![at];
+                let mut state = (at, heading);
+                while let Some((steps, before)) = from.get(&state) {
+                    route.extend(steps.iter().rev());
+                    state = (*route.last().unwrap(), *before);
+                }
+                route.reverse();
+                return Some(route);
+            }
+
+            for side in SIDES {
+                // cells jumped over on the way to the next free one
+                let mut over = vec![];
+                let mut next = side.step(at);
+                while let Some(cell) = next {
+                    let across = matches!(side, Side::East | Side::West);
+                    match self.cell(cell) {
+                        Some(Cell::Along) if across && !self.beside_node(cell) => over.push(cell),
+                        Some(Cell::Across) if !across && !self.beside_node(cell) => over.push(cell),
+                        _ => break,
+                    }
+                    next = side.step(cell);
+                }
+                let Some(next) = next else { continue };
+                let free = self.cell(next) == Some(Cell::Empty) && (!self.beside_node(next) || ends.contains(&next));
+                if !free {
+                    continue;
+                }
+
+                let turn = if side == heading { 0 } else { TURN_COST };
+                let new_cost = c + 1 + turn + over.len() * (1 + CROSSING_COST);
+                if cost.get(&(next, side)).is_none_or(|&best| new_cost < best) {
+                    cost.insert((next, side), new_cost);
+                    over.insert(0, at);
+                    from.insert((next, side), (over, heading));
+                    frontier.push(Reverse((new_cost, (next, side))));
+                }
+            }
+        }
+        None
+    }
+
+    // marks the route on the grid, `ends` are the buildings either side of it
+    fn lay(&mut self, route: &[(usize, usize)], ends: ((usize, usize), (usize, usize))) {
+        for (i, &(x, y)) in route.iter().enumerate() {
+            let before = if i == 0 { ends.0 } else { route[i - 1] };
+            let after = route.get(i + 1).copied().unwrap_or(ends.1);
+            self.grid[y][x] = match self.grid[y][x] {
+                Cell::Empty if before.1 == after.1 => Cell::Across,
+                Cell::Empty if before.0 == after.0 => Cell::Along,
+                Cell::Empty => Cell::Turn,
+                _ => Cell::Crossing,
+            };
+        }
+    }
+}
+
+// place the buildings in columns with a gap between them to run belts in
+// and route every belt. `lifts` are nodes standing on a given foundation,
+// the rest is placed round them. None if a belt can't be routed or a
+// building would stand on a lift
+pub fn place(graph: &BalancerGraph, columns: &[usize], lifts: &[(usize, (usize, usize))], gap: usize, spacing: usize) -> Option<GridLayout> {
+    let fixed = |n: usize| lifts.iter().find(|&&(lift, _)| lift == n).map(|&(_, at)| at);
+    let count = (0..graph.nodes.len()).filter(|&n| fixed(n).is_none()).map(|n| columns[n]).max().unwrap_or(0) + 1;
+
+    // each column in order of where the belts into it come from, so belts
+    // cross as little as they can
+    let mut positions = (0..graph.nodes.len()).map(|n| fixed(n).unwrap_or((0, 0))).collect::<Vec<(usize, usize)>>();
+    for column in 0..count {
+        let nodes = (0..graph.nodes.len()).filter(|&n| columns[n] == column && fixed(n).is_none()).collect::<Vec<usize>>();
+        let feeds = |n: usize| {
+            let incoming = graph.incoming(n);
+            match incoming.is_empty() {
+                true => n as f64,
+                false => incoming.iter().map(|&e| positions[graph.edges[e].from].1 as f64).sum::<f64>() / incoming.len() as f64,
+            }
+        };
+        let mut keyed = nodes.iter().map(|&n| (n, feeds(n))).collect::<Vec<(usize, f64)>>();
+        keyed.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for (row, (n, _)) in keyed.into_iter().enumerate() {
+            positions[n] = (MARGIN + column * (gap + 1), MARGIN + row * spacing);
+        }
+    }
+
+    let width = positions.iter().map(|p| p.0).max().unwrap_or(0) + MARGIN + 1;
+    let height = positions.iter().map(|p| p.1).max().unwrap_or(0) + MARGIN + 1;
+    let mut grid = vec![vec![Cell::Empty; width]; height];
+    for (n, &(x, y)) in positions.iter().enumerate() {
+        if grid[y][x] != Cell::Empty {
+            return None;
+        }
+        grid[y][x] = Cell::Node(n);
+    }
+
+    let lifts = lifts.iter().map(|&(n, _)| n).collect::<Vec<usize>>();
+    let mut router = Router { graph, grid, positions, lifts };
+    let mut routes = vec![vec![]; graph.edges.len()];
+    // shorter belts first, they have the fewest ways round
+    let mut order = (0..graph.edges.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&e| columns[graph.edges[e].to].saturating_sub(columns[graph.edges[e].from]));
+    for e in order {
+        let edge = &router.graph.edges[e];
+        let (from, to) = (edge.from, edge.to);
+        let starts = match router.graph.nodes[from].kind {
+            // a programmable splitter's belts leave by the side it lists them in
+            NodeKind::Filter => {
+                let slot = router.graph.outgoing(from).iter().position(|&x| x == e)?;
+                router.ports(from, &router.exits(from)[slot..=slot])
+            }
+            _ => router.ports(from, &router.exits(from)),
+        };
+        let ends = router.ports(to, &router.entries(to)).into_iter().map(|(c, _)| c).collect::<Vec<_>>();
+        let route = router.route(&starts, &ends)?;
+        router.lay(&route, (router.positions[from], router.positions[to]));
+        routes[e] = route;
+    }
+
+    let Router { grid, positions, lifts, .. } = router;
+    Some(GridLayout {
+        names: graph.nodes.iter().map(|node| node.name()).collect(),
+        kinds: graph.nodes.iter().map(|node| node.kind).collect(),
+        lifts,
+        positions,
+        routes,
+        grid,
+    })
+}
+
+impl BalancerGraph {
+    // a placement on the foundation grid, with buildings in columns facing
+    // east and belts routed between them. the grid is spread out until every
+    // belt finds a way, None if the graph loops or it never does
+    pub fn grid_layout(&self) -> Option<GridLayout> {
+        let columns = columns(self)?;
+        let mut layout = (0..ATTEMPTS).find_map(|i| place(self, &columns, &[], 2 + i, 3 + i))?;
+        if let Some((corner, end)) = layout.bounds() {
+            layout.crop(corner, end);
+        }
+        Some(layout)
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    pub fn layout(&self, result: &BalancerResult) -> Option<GridLayout> {
+        self.graph(result)?.grid_layout()
+    }
+}
+
+impl fmt::Display for GridLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "layout on the {} m foundation grid, {} x {} foundations ({} x {} m):",
+            FOUNDATION, self.width(), self.height(), self.width() * FOUNDATION, self.height() * FOUNDATION
+        )?;
+        self.write_grid(f)?;
+        write!(f, "about {} m of belt", self.belt_length())?;
+        match self.crossings() {
+            0 => Ok(()),
+            n => write!(f, ", {} crossings where a belt goes over another on poles", n),
+        }
+    }
+}
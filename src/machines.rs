@@ -0,0 +1,195 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+// machines are written as `3xconstructor@30` for 3 machines taking 30/min
+// each, with an optional clock speed as in `2xsmelter@30:150%`. the count
+// defaults to 1 and the clock to 100%. miners name their tier and the purity
+// of their node instead, as in `2xmk2@pure:150%`
+
+// clock speeds the game allows
+const MIN_CLOCK: f32 = 1.0;
+const MAX_CLOCK: f32 = 250.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Consumer {
+    pub count: usize,
+    pub name: String,
+    // items/min one machine takes at 100%
+    pub rate: f32,
+    pub clock: f32,
+}
+
+// splits `count x name @ spec : clock%` into its parts
+fn parse_machine(machine: &str) -> Option<(usize, &str, &str, f32)> {
+    let (machine, clock) = match machine.split_once(':') {
+        Some((machine, clock)) => (machine, clock.trim_end_matches('%').parse::<f32>().ok()?),
+        None => (machine, 100.0),
+    };
+    if !(MIN_CLOCK..=MAX_CLOCK).contains(&clock) {
+        return None;
+    }
+
+    let (machine, spec) = machine.split_once('@')?;
+    let (count, name) = match machine.split_once('x') {
+        Some((count, name)) if count.parse::<usize>().is_ok() => (count.parse::<usize>().ok()?, name),
+        _ => (1, machine),
+    };
+    if count == 0 || name.is_empty() {
+        return None;
+    }
+
+    Some((count, name, spec, clock))
+}
+
+impl Consumer {
+    pub fn parse(consumer: &str) -> Option<Consumer> {
+        let (count, name, rate, clock) = parse_machine(consumer)?;
+        let rate = rate.parse::<f32>().ok().filter(|&x| x > 0.0)?;
+
+        Some(Consumer {
+            count,
+            name: name.to_string(),
+            rate,
+            clock,
+        })
+    }
+
+    // what one machine takes at its clock speed
+    pub fn rate(&self) -> f32 {
+        self.rate * self.clock / 100.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Purity {
+    Impure,
+    Normal,
+    Pure,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Miner {
+    pub count: usize,
+    // 1 to 3
+    pub tier: u8,
+    pub purity: Purity,
+    pub clock: f32,
+}
+
+impl Miner {
+    pub fn parse(miner: &str) -> Option<Miner> {
+        let (count, tier, purity, clock) = parse_machine(miner)?;
+        let tier = match tier {
+            "mk1" => 1,
+            "mk2" => 2,
+            "mk3" => 3,
+            _ => return None,
+        };
+        let purity = match purity {
+            "impure" => Purity::Impure,
+            "normal" => Purity::Normal,
+            "pure" => Purity::Pure,
+            _ => return None,
+        };
+
+        Some(Miner {
+            count,
+            tier,
+            purity,
+            clock,
+        })
+    }
+
+    // what one miner extracts at its clock speed, before any belt limit
+    pub fn rate(&self) -> f32 {
+        let base = match self.tier {
+            1 => 60.0,
+            2 => 120.0,
+            _ => 240.0,
+        };
+        let purity = match self.purity {
+            Purity::Impure => 0.5,
+            Purity::Normal => 1.0,
+            Purity::Pure => 2.0,
+        };
+        base * purity * self.clock / 100.0
+    }
+}
+
+// one input belt per miner, none carrying more than the belt can
+pub fn miner_inputs(miners: &[Miner], max_belt: u64) -> Vec<f32> {
+    miners
+        .iter()
+        .flat_map(|m| std::iter::repeat_n(m.rate().min(max_belt as f32), m.count))
+        .collect()
+}
+
+// one output belt per machine
+pub fn consumer_outputs(consumers: &[Consumer]) -> Vec<f32> {
+    consumers
+        .iter()
+        .flat_map(|c| std::iter::repeat_n(c.rate(), c.count))
+        .collect()
+}
+
+// what the inputs supply beyond what the outputs take, negative when they
+// fall short. differences below float noise count as none
+pub fn surplus(supply: f32, demand: f32) -> f32 {
+    let surplus = supply - demand;
+    if surplus.abs() < 0.001 { 0.0 } else { surplus }
+}
+
+// how the consumers add up against what the inputs supply
+pub struct ConsumerReport<'a> {
+    pub consumers: &'a [Consumer],
+    pub supply: f32,
+    // signed, as from `surplus`
+    pub surplus: f32,
+}
+
+impl fmt::Display for ConsumerReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for c in self.consumers {
+            write!(f, "{}x {} at {}/min", c.count, c.name, c.rate())?;
+            if c.clock != 100.0 {
+                write!(f, " ({}% clock)", c.clock)?;
+            }
+            writeln!(f, " = {}/min", c.rate() * c.count as f32)?;
+        }
+
+        let demand = self.supply - self.surplus;
+        write!(f, "outputs take {}/min of {}/min supplied", demand, self.supply)?;
+        if self.surplus > 0.0 {
+            write!(f, ", the {}/min surplus leaves on an overflow output", self.surplus)
+        } else if self.surplus < 0.0 {
+            write!(f, ", {}/min short", -self.surplus)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// what the miners extract and where the belt tier holds them back
+pub struct MinerReport<'a> {
+    pub miners: &'a [Miner],
+    pub max_belt: u64,
+}
+
+impl fmt::Display for MinerReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, m) in self.miners.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}x mk{} miner on a {} node", m.count, m.tier, format!("{:?}", m.purity).to_lowercase())?;
+            if m.clock != 100.0 {
+                write!(f, " at {}% clock", m.clock)?;
+            }
+            write!(f, " = {}/min", m.rate())?;
+            if m.rate() > self.max_belt as f32 {
+                write!(f, ", capped to {}/min by the belt", self.max_belt)?;
+            }
+        }
+        Ok(())
+    }
+}
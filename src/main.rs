@@ -1,31 +1,52 @@
-use crate::balancer::Balancer;
-use crate::cmd::{read_input, Args};
+use crate::editor::Editor;
+use crate::repl::Session;
 
 mod cmd;
 mod calc;
 mod operation;
 mod helpers;
+#[cfg(test)]
 mod tests;
 mod balancer;
+mod table;
+mod parallel;
+mod ida;
+mod intern;
+mod bench;
+mod graph;
+mod sim;
+mod backpressure;
+mod rational;
+mod flow;
+mod network;
+mod repl;
+mod batch;
+mod machines;
+mod fluid;
+mod manifold;
+mod strict;
+mod starvation;
+mod scenario;
+mod sushi;
+mod priority;
+mod saturation;
+mod burst;
+mod layout;
+mod floors;
+mod svg;
+mod editor;
 
 fn main() {
+    let mut session = Session::new();
+    let mut editor = Editor::new();
     loop {
-        let input = read_input();
-        let args = Args::new(input);
-        // println!("{:?}",args);
-        if args.quit {
-            println!("Quitting");
+        let input = editor.read_line();
+        // end of input, e.g. a piped problem list
+        if input.is_empty() {
             break;
         }
-        if args.inputs.len() == 0 {
-            panic!("No input belts given");
-        }
-        if args.outputs.len() == 0 {
-            panic!("No output belts given");
+        if !session.handle(&input) {
+            break;
         }
-        let balancer = Balancer::new(args);
-        let result = balancer.find_ideal_balance();
-
-        println!("{}", result);
     }
 }
@@ -0,0 +1,158 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{BalancerGraph, Edge, NodeKind};
+use crate::sim::Simulator;
+use std::fmt;
+use std::fmt::Formatter;
+
+// a minute of consumption is what counts as receiving the full rate
+const WINDOW: u64 = 60;
+// give up on filling after an hour
+const MAX_FILL: u64 = 3600;
+
+// the manifold for a set of belts: the inputs merged onto one main line and
+// a chain of splitters along it, each feeding up to two outputs and passing
+// the rest on. outputs that back up make their splitter overflow down the line.
+// belts carry their steady state rate
+pub fn manifold_graph(inputs: &[u64], outputs: &[u64]) -> BalancerGraph {
+    let mut graph = BalancerGraph::default();
+    let mut line = vec![];
+    for &rate in inputs {
+        line.push((graph.add_node(NodeKind::Input), rate));
+    }
+
+    // merge three belts at a time until one main line is left
+    while line.len() > 1 {
+        let merger = graph.add_node(NodeKind::Merger);
+        let merged = line.drain(..line.len().min(3)).collect::<Vec<_>>();
+        for &(from, rate) in &merged {
+            graph.edges.push(Edge { from, to: merger, rate });
+        }
+        line.insert(0, (merger, merged.iter().map(|(_, rate)| rate).sum()));
+    }
+    let Some(&(mut from, mut rate)) = line.first() else { return graph };
+
+    let mut remaining = outputs;
+    while !remaining.is_empty() {
+        if remaining.len() == 1 {
+            let out = graph.add_node(NodeKind::Output);
+            graph.edges.push(Edge { from, to: out, rate });
+            break;
+        }
+
+        let splitter = graph.add_node(NodeKind::Splitter);
+        graph.edges.push(Edge { from, to: splitter, rate });
+        // the last splitter hands everything out, the others keep one side for the line
+        let fed = if remaining.len() <= 3 { remaining.len() } else { 2 };
+        for &out_rate in &remaining[..fed] {
+            let out = graph.add_node(NodeKind::Output);
+            graph.edges.push(Edge { from: splitter, to: out, rate: out_rate });
+            rate -= out_rate;
+        }
+        remaining = &remaining[fed..];
+        from = splitter;
+    }
+
+    graph
+}
+
+pub struct ManifoldReport {
+    pub manifold_buildings: usize,
+    // None if the search found no balancer
+    pub balancer_buildings: Option<usize>,
+    pub names: Vec<String>,
+    pub expected: Vec<u64>,
+    // what the manifold settles on once full
+    pub measured: Vec<u64>,
+    // seconds until every output gets its rate
+    pub manifold_fill: Option<u64>,
+    pub balancer_fill: Option<u64>,
+    pub main_line: u64,
+    pub max_belt: u64,
+}
+
+impl ManifoldReport {
+    pub fn matches(&self) -> bool {
+        self.expected.iter().zip(&self.measured).all(|(&e, &m)| {
+            e.abs_diff(m) <= (e / 100).max(10_000_000)
+        })
+    }
+}
+
+// outputs consume exactly their requested rate, like machines would
+fn fill_time(graph: &BalancerGraph, max_belt: u64, outputs: &[u64]) -> Option<u64> {
+    let mut sim = Simulator::new(graph, max_belt);
+    sim.demand = outputs.iter().map(|&x| Some(x)).collect();
+    sim.fill_time(WINDOW, MAX_FILL)
+}
+
+// balancer related functions
+impl Balancer {
+    // simulate the manifold for the same belts and weigh it against the solved balancer
+    pub fn manifold(&self, result: &BalancerResult) -> ManifoldReport {
+        let graph = manifold_graph(&self.inputs, &self.outputs);
+        let buildings = graph.nodes
+            .iter()
+            .filter(|n| matches!(n.kind, NodeKind::Splitter | NodeKind::Merger))
+            .count();
+
+        let mut sim = Simulator::new(&graph, self.max_belt);
+        sim.demand = self.outputs.iter().map(|&x| Some(x)).collect();
+        let measured = sim.run(MAX_FILL, 6000).outputs;
+
+        let balancer = self.graph(result).filter(|_| !result.path().is_empty());
+
+        ManifoldReport {
+            manifold_buildings: buildings,
+            balancer_buildings: balancer.as_ref().map(|_| result.path().len()),
+            names: self.output_names(),
+            expected: self.outputs.clone(),
+            measured,
+            manifold_fill: fill_time(&graph, self.max_belt, &self.outputs),
+            balancer_fill: balancer.and_then(|g| fill_time(&g, self.max_belt, &self.outputs)),
+            main_line: self.inputs.iter().sum(),
+            max_belt: self.max_belt,
+        }
+    }
+}
+
+impl fmt::Display for ManifoldReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let seconds = |fill: Option<u64>| match fill {
+            Some(t) => format!("{}s", t),
+            None => format!("over {}s", MAX_FILL),
+        };
+
+        writeln!(f, "manifold: {} buildings, every output at its rate after {}",
+            self.manifold_buildings, seconds(self.manifold_fill)
+        )?;
+        for ((name, e), m) in self.names.iter().zip(&self.expected).zip(&self.measured) {
+            writeln!(f, "  {}: {:.2} requested, {:.2} once full", name, *e as f64 / 1e8, *m as f64 / 1e8)?;
+        }
+        if self.main_line > self.max_belt {
+            writeln!(f, "  the main line needs {:.2}, over the {} belt limit",
+                self.main_line as f64 / 1e8, self.max_belt as f64 / 1e8
+            )?;
+        }
+
+        let Some(balancer) = self.balancer_buildings else {
+            return write!(f, "no balancer to compare with");
+        };
+        writeln!(f, "balancer: {} buildings, every output at its rate after {}",
+            balancer, seconds(self.balancer_fill)
+        )?;
+
+        if !self.matches() {
+            write!(f, "the manifold never settles on the requested rates, the balancer is worth it")
+        } else if balancer <= self.manifold_buildings {
+            write!(f, "the balancer needs no more buildings than the manifold, it is worth it")
+        } else {
+            write!(f, "the manifold saves {} buildings", balancer - self.manifold_buildings)?;
+            if let (Some(b), Some(m)) = (self.balancer_fill, self.manifold_fill) {
+                if b < m {
+                    write!(f, ", the balancer reaches every rate {}s sooner", m - b)?;
+                }
+            }
+            write!(f, ", the balancer is only worth it if the outputs can't wait for the manifold to fill")
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::graph::{BalancerGraph, NodeKind};
+use std::fmt;
+use std::fmt::Formatter;
+
+// text format for balancer networks, one source per statement:
+//
+//   # comments run to the end of the line
+//   in1 -> S1 (120)
+//   S1 -> M1 (40), out2 (80); M1 -> out1:iron_b
+//
+// statements end at a newline or `;`, rates in brackets are items/min and
+// may be left out when they aren't known. inputs and outputs can carry a label
+// after a colon, and a belt out of an F splitter, smart or programmable, can be
+// marked overflow.
+// `BalancerGraph` prints in this format
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Number(String),
+    Arrow,
+    Comma,
+    Open,
+    Close,
+    End,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer<'_> {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, line: usize, column: usize, message: String) -> ParseError {
+        ParseError { line, column, message }
+    }
+
+    // next token and where it starts, None at the end of the text
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, ParseError> {
+        loop {
+            match self.chars.peek() {
+                Some('#') => {
+                    while self.chars.peek().is_some_and(|&c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                Some(c) if c.is_whitespace() && *c != '\n' => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+
+        let (line, column) = (self.line, self.column);
+        let Some(c) = self.bump() else { return Ok(None) };
+        let token = match c {
+            '\n' | ';' => Token::End,
+            ',' => Token::Comma,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '-' if self.chars.peek() == Some(&'>') => {
+                self.bump();
+                Token::Arrow
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some(&c) = self.chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == ':') {
+                    name.push(c);
+                    self.bump();
+                }
+                Token::Name(name)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(&c) = self.chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                    number.push(c);
+                    self.bump();
+                }
+                Token::Number(number)
+            }
+            c => return Err(self.error(line, column, format!("unexpected `{}`", c))),
+        };
+
+        Ok(Some((token, line, column)))
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize, usize)>, ParseError> {
+    let mut lexer = Lexer {
+        chars: text.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+    let mut tokens = vec![];
+    while let Some(token) = lexer.next_token()? {
+        tokens.push(token);
+    }
+    // a last statement without a newline still needs ending
+    tokens.push((Token::End, lexer.line, lexer.column));
+
+    Ok(tokens)
+}
+
+// labels start with a letter and go on with letters, digits and underscores,
+// names of nodes like in1 or S2 are taken
+pub fn is_label(label: &str) -> bool {
+    label.starts_with(|c: char| c.is_ascii_alphabetic())
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && BalancerGraph::default().node_by_name(label).is_none()
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Name(name) => format!("`{}`", name),
+        Token::Number(number) => format!("`{}`", number),
+        Token::Arrow => "`->`".to_string(),
+        Token::Comma => "`,`".to_string(),
+        Token::Open => "`(`".to_string(),
+        Token::Close => "`)`".to_string(),
+        Token::End => "end of statement".to_string(),
+    }
+}
+
+// parses a network, belts keep the order they are written in
+pub fn parse_network(text: &str) -> Result<BalancerGraph, ParseError> {
+    let tokens = tokenize(text)?;
+    let mut graph = BalancerGraph::default();
+    let mut pos = 0;
+
+    let error = |pos: usize, message: String| {
+        let (_, line, column) = tokens[pos];
+        ParseError { line, column, message }
+    };
+
+    let node = |graph: &mut BalancerGraph, pos: &mut usize| -> Result<usize, ParseError> {
+        match &tokens[*pos].0 {
+            Token::Name(name) => {
+                let (name, label) = match name.split_once(':') {
+                    Some((name, label)) => (name, Some(label)),
+                    None => (name.as_str(), None),
+                };
+                let node = graph
+                    .node_by_name(name)
+                    .ok_or_else(|| error(*pos, format!("`{}` is not a node, expected in1, out1, S1 or M1", name)))?;
+                if let Some(label) = label {
+                    if !matches!(graph.nodes[node].kind, NodeKind::Input | NodeKind::Output) || !is_label(label) {
+                        return Err(error(*pos, format!("`{}` can't be labelled `{}`", name, label)));
+                    }
+                    graph.nodes[node].label = Some(label.to_string());
+                }
+                *pos += 1;
+                Ok(node)
+            }
+            token => Err(error(*pos, format!("expected a node name, found {}", describe(token)))),
+        }
+    };
+
+    while pos + 1 < tokens.len() {
+        if tokens[pos].0 == Token::End {
+            pos += 1;
+            continue;
+        }
+
+        let from = node(&mut graph, &mut pos)?;
+        if tokens[pos].0 != Token::Arrow {
+            return Err(error(pos, format!("expected `->`, found {}", describe(&tokens[pos].0))));
+        }
+        pos += 1;
+
+        loop {
+            let to = node(&mut graph, &mut pos)?;
+            graph.connect(from, to);
+
+            if tokens[pos].0 == Token::Open {
+                let rate = match &tokens[pos + 1].0 {
+                    Token::Number(number) => number
+                        .parse::<f64>()
+                        .map_err(|_| error(pos + 1, format!("`{}` is not a rate", number)))?,
+                    token => return Err(error(pos + 1, format!("expected a rate, found {}", describe(token)))),
+                };
+                if tokens[pos + 2].0 != Token::Close {
+                    return Err(error(pos + 2, format!("expected `)`, found {}", describe(&tokens[pos + 2].0))));
+                }
+                graph.edges.last_mut().unwrap().rate = (rate * 1e8).round() as u64;
+                pos += 3;
+            }
+            if tokens[pos].0 == Token::Name("overflow".to_string()) {
+                if graph.nodes[from].kind != NodeKind::Filter {
+                    return Err(error(pos, format!("only smart or programmable splitters (F) have overflow belts, not {}", graph.nodes[from].name())));
+                }
+                graph.overflow.push(graph.edges.len() - 1);
+                pos += 1;
+            }
+
+            match tokens[pos].0 {
+                Token::Comma => pos += 1,
+                Token::End => break,
+                ref token => return Err(error(pos, format!("expected `,` or end of statement, found {}", describe(token)))),
+            }
+        }
+    }
+
+    Ok(graph)
+}
@@ -1,3 +1,4 @@
+use crate::cmd::CostModel;
 use std::fmt;
 use std::fmt::Formatter;
 
@@ -11,18 +12,47 @@ pub enum Operation {
     Split {
         input: u64,
         output: (Option<u64>, Option<u64>, Option<u64>),
+    },
+    // a programmable or smart splitter, `items` names what each output is set
+    // to take: an item, "any" for everything else or "overflow" for whatever
+    // the other outputs can't take
+    Filter {
+        input: u64,
+        output: (Option<u64>, Option<u64>, Option<u64>),
+        items: Vec<String>,
     }
 }
 
 impl Operation {
     // cost function for giving priority to different operations,
     // higher values are higher priority when used in the queue
-    pub fn cost(&self) -> i64 {
-        match self {
+    pub fn cost(&self, model: CostModel) -> i64 {
+        match (self, model) {
             // if we call cost on Err something has gone horribly wrong
-            Operation::Err => 0,
-            Operation::Split {input: _, output: _} => 1,
-            Operation::Merge {input: _, output: _} => 1,
+            (Operation::Err, _) => 0,
+            (Operation::Split {input: _, output: _} | Operation::Filter { .. }, CostModel::Splitters) => 2,
+            (Operation::Merge {input: _, output: _}, CostModel::Mergers) => 2,
+            _ => 1,
+        }
+    }
+
+    // rates going into the building
+    pub fn inputs(&self) -> [Option<u64>; 3] {
+        match self {
+            Operation::Err => [None; 3],
+            Operation::Split {input, output: _} => [Some(*input), None, None],
+            Operation::Merge {input, output: _} => [input.0, input.1, input.2],
+            Operation::Filter { input, .. } => [Some(*input), None, None],
+        }
+    }
+
+    // rates coming out of the building
+    pub fn outputs(&self) -> [Option<u64>; 3] {
+        match self {
+            Operation::Err => [None; 3],
+            Operation::Split {input: _, output} => [output.0, output.1, output.2],
+            Operation::Merge {input: _, output} => [Some(*output), None, None],
+            Operation::Filter { output, .. } => [output.0, output.1, output.2],
         }
     }
 }
@@ -46,18 +76,33 @@ impl fmt::Display for Operation {
                 }
             }
             Operation::Merge {input, output} => {
+                // a merge over the belt limit only passes on a full belt
+                let saturated = input.0.unwrap_or(0) + input.1.unwrap_or(0) + input.2.unwrap_or(0) > *output;
                 let output = *output as f64 / 1e8;
 
                 if input.2.is_none() {
                     let h1 = input.0.unwrap() as f64 / 1e8;
                     let h2 = input.1.unwrap() as f64 / 1e8;
-                    write!(f, "merge {}, {} -> {}", h1, h2, output)
+                    write!(f, "merge {}, {} -> {}", h1, h2, output)?;
                 }else {
                     let t1 = input.0.unwrap() as f64 / 1e8;
                     let t2 = input.1.unwrap() as f64 / 1e8;
                     let t3 = input.2.unwrap() as f64 / 1e8;
-                    write!(f, "merge {}, {}, {} -> {}", t1, t2, t3, output)
+                    write!(f, "merge {}, {}, {} -> {}", t1, t2, t3, output)?;
                 }
+                if saturated {
+                    write!(f, " (saturated)")?;
+                }
+                Ok(())
+            }
+            Operation::Filter { input, output, items } => {
+                let parts = [output.0, output.1, output.2]
+                    .iter()
+                    .flatten()
+                    .zip(items)
+                    .map(|(rate, item)| format!("{} {}", item, *rate as f64 / 1e8))
+                    .collect::<Vec<String>>();
+                write!(f, "filter {} -> {}", *input as f64 / 1e8, parts.join(", "))
             }
         }
     }
@@ -69,15 +114,18 @@ impl PartialEq for Operation {
         let mut is_err = false;
         let mut is_merge = false;
         let mut is_split = false;
+        let mut is_filter = false;
         match self {
             Operation::Err => { is_err = true;},
             Operation::Split { input: _, output: _} => { is_split = true; },
             Operation::Merge {input: _, output: _} => { is_merge = true; },
+            Operation::Filter { .. } => { is_filter = true; },
         }
         match other {
             Operation::Err => { is_err },
             Operation::Split {input: _, output: _} => { is_split },
             Operation::Merge {input: _, output: _} => { is_merge },
+            Operation::Filter { .. } => { is_filter },
         }
     }
 }
@@ -96,12 +144,8 @@ pub enum ReverseOperation {
 }
 
 impl ReverseOperation {
-    pub fn cost(&self) -> i64 {
-        match self {
-            ReverseOperation::Err => 0,
-            ReverseOperation::Split {input: _, output: _} => 1,
-            ReverseOperation::Merge {input: _, output: _} => 1,
-        }
+    pub fn cost(&self, model: CostModel) -> i64 {
+        self.forward().cost(model)
     }
 
     pub fn forward(&self) -> Operation {
@@ -0,0 +1,237 @@
+use crate::balancer::{Balancer, BalancerResult, BalancerState};
+use crate::helpers::multiset;
+use crate::intern::{StateArena, StateId};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+type CameFrom<O> = HashMap<StateId, (Option<O>, Option<StateId>)>;
+
+// state shared between the forward and backward search threads
+// locks are always taken cost map first, then arena
+struct Shared {
+    arena: Mutex<StateArena>,
+    cost_fwd: Mutex<HashMap<StateId, i64>>,
+    cost_bkwd: Mutex<HashMap<StateId, i64>>,
+    meeting_point: Mutex<Option<(i64, StateId)>>,
+    done: AtomicBool,
+    states_checked: AtomicU64,
+    total_states: AtomicU64,
+    workers: usize,
+    gcd: u64,
+    start: Instant,
+}
+
+// balancer related functions
+impl Balancer {
+    // expands the forward and backward frontiers at the same time, one thread
+    // per direction, each splitting its batch of popped states across
+    // a pool of half the requested worker threads, kept for the whole search
+    pub fn find_ideal_balance_parallel(&self, start: Instant) -> BalancerResult {
+        let initial_state = multiset(self.inputs.clone());
+        let target_state = multiset(self.outputs.clone());
+        let gcd = self.search_gcd();
+
+        let mut arena = StateArena::new(gcd);
+        let initial_id = arena.intern(&initial_state);
+        let target_id = arena.intern(&target_state);
+
+        let shared = Shared {
+            arena: Mutex::new(arena),
+            cost_fwd: Mutex::new(HashMap::from([(initial_id, 0i64)])),
+            cost_bkwd: Mutex::new(HashMap::from([(target_id, 0i64)])),
+            meeting_point: Mutex::new(None),
+            done: AtomicBool::new(false),
+            states_checked: AtomicU64::new(0),
+            total_states: AtomicU64::new(0),
+            workers: (self.threads / 2).max(1),
+            gcd,
+            start,
+        };
+
+        let (from_fwd, from_bkwd) = thread::scope(|s| {
+            let fwd = s.spawn(|| self.search_direction(
+                (initial_id, &initial_state),
+                &target_state,
+                |state| self.get_states_fwd(state, gcd).collect(),
+                |op| op.cost(self.cost_model),
+                true,
+                &shared,
+            ));
+            let bkwd = s.spawn(|| self.search_direction(
+                (target_id, &target_state),
+                &initial_state,
+                |state| self.get_states_bkwd(state, gcd),
+                |op| op.cost(self.cost_model),
+                false,
+                &shared,
+            ));
+            (fwd.join().unwrap(), bkwd.join().unwrap())
+        });
+
+        let meeting_point = shared.meeting_point.into_inner().unwrap();
+        if let Some((_, meeting)) = meeting_point {
+            return Self::build_path(
+                meeting,
+                initial_id,
+                target_id,
+                &shared.arena.into_inner().unwrap(),
+                from_fwd,
+                from_bkwd,
+                shared.total_states.into_inner(),
+                shared.states_checked.into_inner(),
+                start.elapsed().as_secs_f64()
+            );
+        }
+
+        println!("No Solution Found after {}s :(", start.elapsed().as_secs());
+        // no path, but keep the counts so a timed out search can be compared
+        BalancerResult::new(
+            vec![],
+            shared.total_states.into_inner(),
+            shared.states_checked.into_inner(),
+            start.elapsed().as_secs_f64()
+        )
+    }
+
+    // a* from `from` towards `to`, stops once either direction has found a meeting point
+    fn search_direction<O, E, C>(
+        &self,
+        (from_id, from): (StateId, &BalancerState),
+        to: &BalancerState,
+        expand: E,
+        cost_of: C,
+        fwd: bool,
+        shared: &Shared,
+    ) -> CameFrom<O>
+    where
+        O: Clone + Send,
+        E: Fn(&Vec<u64>) -> Vec<(O, BalancerState)> + Sync,
+        C: Fn(&O) -> i64,
+    {
+        let (own_cost, other_cost) = if fwd {
+            (&shared.cost_fwd, &shared.cost_bkwd)
+        } else {
+            (&shared.cost_bkwd, &shared.cost_fwd)
+        };
+        let gcd = shared.gcd;
+        let goal = to.to_vec();
+        let mut frontier = BinaryHeap::new();
+        frontier.push((Reverse(Self::heuristic(&from.to_vec(), &goal, gcd)), 0i64, from_id));
+
+        let mut came_from: CameFrom<O> = HashMap::new();
+        came_from.insert(from_id, (None, None));
+
+        // successors of a state with their heuristics, worked out without
+        // holding any of the shared locks
+        let work = |state: &Vec<u64>| {
+            expand(state)
+                .into_iter()
+                .map(|(action, next)| {
+                    let estimate = Self::heuristic(&next.to_vec(), &goal, gcd);
+                    (action, next, estimate)
+                })
+                .collect::<Vec<(O, BalancerState, i64)>>()
+        };
+
+        // one pool of workers for the whole search, fed the states of each
+        // batch as (place in batch, state) and handing back their successors
+        let (jobs, queue) = mpsc::channel::<(usize, Vec<u64>)>();
+        let (done, results) = mpsc::channel();
+        let queue = Mutex::new(queue);
+        thread::scope(|s| {
+            if shared.workers > 1 {
+                for _ in 0..shared.workers {
+                    let (queue, done, work) = (&queue, done.clone(), &work);
+                    s.spawn(move || {
+                        // the queue closes once the search is over
+                        while let Ok((i, state)) = queue.lock().unwrap().recv() {
+                            if done.send((i, work(&state))).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+            }
+
+            while !frontier.is_empty() &&
+                !shared.done.load(Ordering::Relaxed) &&
+                shared.start.elapsed().as_secs() < self.time_limit {
+                let mut batch = vec![];
+                while batch.len() < shared.workers {
+                    match frontier.pop() {
+                        Some((_, cost, current)) => batch.push((cost, current)),
+                        None => break,
+                    }
+                }
+                shared.states_checked.fetch_add(batch.len() as u64, Ordering::Relaxed);
+
+                {
+                    let other_cost = other_cost.lock().unwrap();
+                    for (cost, current) in &batch {
+                        if let Some(&rev_cost) = other_cost.get(current) {
+                            let mut meeting = shared.meeting_point.lock().unwrap();
+                            if meeting.as_ref().is_none_or(|(best, _)| cost + rev_cost < *best) {
+                                *meeting = Some((cost + rev_cost, *current));
+                            }
+                            shared.done.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+                if shared.done.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                // generate successors for the whole batch on the pool
+                let values = {
+                    let arena = shared.arena.lock().unwrap();
+                    batch.iter().map(|(_, current)| arena.to_vec(*current)).collect::<Vec<_>>()
+                };
+                let expanded = if values.len() == 1 || shared.workers == 1 {
+                    values.iter().map(&work).collect::<Vec<_>>()
+                } else {
+                    let count = values.len();
+                    for job in values.into_iter().enumerate() {
+                        let _ = jobs.send(job);
+                    }
+                    let mut expanded = (0..count).map(|_| vec![]).collect::<Vec<_>>();
+                    for (i, next_states) in results.iter().take(count) {
+                        expanded[i] = next_states;
+                    }
+                    expanded
+                };
+
+                // the locks are only held to intern the successors and keep
+                // the cheaper cost, the frontier and parents are this thread's own
+                let mut improved = vec![];
+                {
+                    let mut own_cost = own_cost.lock().unwrap();
+                    let mut arena = shared.arena.lock().unwrap();
+                    for ((cost, current), next_states) in batch.iter().zip(expanded) {
+                        shared.total_states.fetch_add(next_states.len() as u64, Ordering::Relaxed);
+
+                        for (action, next, estimate) in next_states {
+                            let new_cost = cost + cost_of(&action);
+                            let next_id = arena.intern(&next);
+
+                            if own_cost.get(&next_id).is_none_or(|&prev_cost| new_cost < prev_cost) {
+                                own_cost.insert(next_id, new_cost);
+                                improved.push((next_id, new_cost, estimate + cost, action, *current));
+                            }
+                        }
+                    }
+                }
+                for (next_id, new_cost, confidence, action, current) in improved {
+                    came_from.insert(next_id, (Some(action), Some(current)));
+                    frontier.push((Reverse(confidence), new_cost, next_id));
+                }
+            }
+            drop(jobs);
+        });
+
+        came_from
+    }
+}
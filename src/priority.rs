@@ -0,0 +1,214 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{Builder, Edge, Node, NodeKind};
+use crate::helpers::multiset;
+use crate::operation::Operation;
+use crate::sim::Simulator;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Instant;
+
+// share of the full supply each scenario of the report runs at
+const SUPPLY_STEPS: [u64; 4] = [100, 75, 50, 25];
+
+// balancer related functions
+impl Balancer {
+    // outputs asking to be fed in some order, it takes two different priorities
+    pub fn is_priority(&self) -> bool {
+        let mut priorities = (0..self.outputs.len()).map(|i| self.priority(i)).collect::<Vec<_>>();
+        priorities.dedup();
+        priorities.len() > 1
+    }
+
+    // the priority of an output, outputs without one sort after every number
+    fn priority(&self, output: usize) -> u32 {
+        self.priorities.get(output).copied().flatten().unwrap_or(u32::MAX)
+    }
+
+    // outputs grouped by priority, the group fed first comes first
+    fn priority_groups(&self) -> Vec<Vec<usize>> {
+        let mut outputs = (0..self.outputs.len()).collect::<Vec<usize>>();
+        outputs.sort_by_key(|&i| self.priority(i));
+        outputs.chunk_by(|&a, &b| self.priority(a) == self.priority(b)).map(|g| g.to_vec()).collect()
+    }
+
+    // a balancer that feeds outputs in priority order. the inputs are merged
+    // onto one line and a smart splitter per priority hands its group what the
+    // group draws and overflows the rest down the line, so under a shortage the
+    // later groups go short first. outputs of the same priority share their
+    // feed through a balancer from the normal search
+    pub fn find_priority_balance(&self, start: Instant) -> BalancerResult {
+        let total = self.inputs.iter().sum::<u64>();
+        if total > self.max_belt {
+            println!("The priority line would carry {}, over the {} belt limit",
+                total as f64 / 1e8, self.max_belt as f64 / 1e8
+            );
+            return BalancerResult::default();
+        }
+
+        let mut builder = Builder::new(&self.inputs);
+        let mut line = self.inputs
+            .iter()
+            .map(|&rate| (builder.graph.add_node(NodeKind::Input), rate))
+            .collect::<Vec<(usize, u64)>>();
+        while line.len() > 1 {
+            let merged = builder.merge(&line[..line.len().min(3)]);
+            line.drain(..line.len().min(3));
+            line.insert(0, merged);
+        }
+        let mut line = line[0];
+
+        let mut rest = self.clone();
+        rest.priorities = vec![];
+        let (mut total, mut checked, mut searched) = (0, 0, false);
+        let mut smart = vec![];
+        let groups = self.priority_groups();
+        for (g, group) in groups.iter().enumerate() {
+            rest.outputs = group.iter().map(|&i| self.outputs[i]).collect();
+            let fed = rest.outputs.iter().sum::<u64>();
+            let feed = if g + 1 == groups.len() {
+                line
+            } else {
+                let splitter = builder.graph.add_node(NodeKind::Filter);
+                builder.graph.edges.push(Edge { from: line.0, to: splitter, rate: line.1 });
+                builder.push(Operation::Filter {
+                    input: line.1,
+                    output: (Some(fed), Some(line.1 - fed), None),
+                    items: vec!["any".to_string(), "overflow".to_string()],
+                });
+                smart.push(splitter);
+                line = (splitter, line.1 - fed);
+                (splitter, fed)
+            };
+
+            rest.inputs = vec![fed];
+            let mut path = vec![];
+            if multiset(rest.inputs.clone()) != multiset(rest.outputs.clone()) {
+                let result = rest.find_ideal_balance_since(start);
+                if result.path().is_empty() {
+                    return BalancerResult::default();
+                }
+                searched |= !result.is_canonical();
+                total += result.total_states();
+                checked += result.checked_states();
+                path = result.path().to_vec();
+            }
+
+            // the group is laid out before the line goes on, so the belt a smart
+            // splitter feeds its group with comes before its overflow belt
+            let Some(mut open) = builder.graph.lay_out_path(vec![feed], &path) else {
+                return BalancerResult::default();
+            };
+            for (op, _) in path {
+                builder.push(op);
+            }
+            for &i in group {
+                let Some(k) = open.iter().position(|&(_, rate)| rate == self.outputs[i]) else {
+                    return BalancerResult::default();
+                };
+                let (from, rate) = open.remove(k);
+                // outputs keep the number they were asked for with, not their place in the line
+                builder.graph.nodes.push(Node { kind: NodeKind::Output, index: i + 1, label: None });
+                builder.graph.edges.push(Edge { from, to: builder.graph.nodes.len() - 1, rate });
+            }
+        }
+
+        let Builder { mut graph, path, .. } = builder;
+        graph.overflow = smart.iter().map(|&s| graph.outgoing(s)[1]).collect();
+        let time = start.elapsed().as_secs_f64();
+        let result = if searched {
+            BalancerResult::new(path, total, checked, time)
+        } else {
+            BalancerResult::canonical(path, time)
+        };
+        result.with_graph(graph)
+    }
+
+    // simulate the plan with every input cut back to a share of its rate and
+    // outputs drawing their requested rate, and compare what each output gets
+    // with feeding the groups strictly in order
+    pub fn fill_order(&self, result: &BalancerResult) -> Option<FillOrderReport> {
+        let graph = self.graph(result)?;
+        let mut sim = Simulator::new(&graph, self.max_belt);
+        sim.demand = self.outputs.iter().map(|&x| Some(x)).collect();
+
+        let scenarios = SUPPLY_STEPS
+            .iter()
+            .map(|&share| {
+                sim.supply = self.inputs.iter().map(|&x| x * share / 100).collect();
+                FillScenario {
+                    share,
+                    expected: self.filled(self.inputs.iter().sum::<u64>() * share / 100),
+                    measured: sim.run(600, 6000).outputs,
+                }
+            })
+            .collect();
+
+        Some(FillOrderReport {
+            names: self.output_names(),
+            priorities: (0..self.outputs.len()).map(|i| self.priorities.get(i).copied().flatten()).collect(),
+            scenarios,
+        })
+    }
+
+    // what each output gets from `supply` when groups are fed strictly in
+    // order, a group that can't be fed in full shares in proportion to its rates
+    fn filled(&self, mut supply: u64) -> Vec<u64> {
+        let mut expected = vec![0; self.outputs.len()];
+        for group in self.priority_groups() {
+            let wanted = group.iter().map(|&i| self.outputs[i]).sum::<u64>();
+            let given = wanted.min(supply);
+            for i in group {
+                expected[i] = (self.outputs[i] as u128 * given as u128 / wanted as u128) as u64;
+            }
+            supply -= given;
+        }
+        expected
+    }
+}
+
+pub struct FillScenario {
+    // percent of the full supply
+    pub share: u64,
+    pub expected: Vec<u64>,
+    pub measured: Vec<u64>,
+}
+
+impl FillScenario {
+    // within 2% of the rate or 0.5 items/min, the overflow only kicks in once
+    // a belt is full so it lags by the odd item
+    pub fn holds(&self) -> bool {
+        self.expected.iter().zip(&self.measured).all(|(&e, &m)| {
+            e.abs_diff(m) <= (e / 50).max(50_000_000)
+        })
+    }
+}
+
+pub struct FillOrderReport {
+    pub names: Vec<String>,
+    pub priorities: Vec<Option<u32>>,
+    pub scenarios: Vec<FillScenario>,
+}
+
+impl fmt::Display for FillOrderReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for scenario in &self.scenarios {
+            writeln!(f, "supply at {}%:", scenario.share)?;
+            for (i, (e, m)) in scenario.expected.iter().zip(&scenario.measured).enumerate() {
+                let priority = match self.priorities[i] {
+                    Some(p) => format!("priority {}", p),
+                    None => "no priority".to_string(),
+                };
+                writeln!(f, "  {} ({}): {:.2} in priority order, {:.2} simulated",
+                    self.names[i], priority, *e as f64 / 1e8, *m as f64 / 1e8
+                )?;
+            }
+        }
+
+        let broken = self.scenarios.iter().filter(|s| !s.holds()).map(|s| format!("{}%", s.share)).collect::<Vec<_>>();
+        if broken.is_empty() {
+            write!(f, "outputs fill in priority order at every supply")
+        } else {
+            write!(f, "the fill order does not hold at {} supply", broken.join(", "))
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::batch::run_batch;
+use crate::bench::run_benchmarks;
+use crate::cmd::{split_network, Args};
+use crate::flow::solve_flow;
+use crate::graph::NodeKind;
+use crate::machines::{ConsumerReport, MinerReport};
+use crate::network::parse_network;
+
+const HELP: &str = "\
+problems:
+  -in 120 -out 40 40 40     solve a balancer, 60x2 repeats a belt
+  -in ore=120 -out a=60 b=60
+                            label belts, labels name them in the network and reports
+  -miners 2xmk2@pure mk3@normal:250%
+                            inputs from miners on impure, normal or pure nodes
+  -consumers 3xconstructor@30 2xsmelter@30:150%
+                            outputs from machines, a clock speed is optional
+  -mb 780 | mk5             belt limit       -t 4         search threads
+  -alg astar|ida            search algorithm -tl 30       time limit in seconds
+  -cost buildings|splitters|mergers
+                            count every building the same or a splitter or merger as two
+  -in iron:60+copper:30 -out iron:30 iron:30+copper:30
+                            sort mixed belts with programmable splitters
+  -out power=120!1 sink=60!2 feed outputs in priority order, 1 first
+  -fluid mk1|mk2            plan pipes and junctions of 300 or 600 m³/min
+  -sim                      simulate the result
+  -strict                   every output takes the same share of every input
+  -saturate                 let merges run over the belt limit and back up, -in may offer more than -out takes
+  -layout                   place the result on the 8 m foundation grid
+  -floor 12x10              stack the layout over floors of at most 12 x 10 foundations, linked by lifts
+  -manifold                 compare the result with a manifold for the same belts
+  -block 2 3@15             block output 2, limit output 3 to 15/min
+  -starve 1 2@30            stop input 1, drop input 2 to 30/min
+  -burst 1@480/2400         input 1 unloads at up to 480/min from 2400 items, its -in rate is the average
+  -save file                write the result in the network format
+  -svg file                 draw the result to an svg image, belts coloured by tier
+  -load file | -net text    solve the flow of a hand built network
+  -batch file               solve every problem in a file, one per line
+  -report file              write the batch report to a file
+  -bench                    run the search benchmarks
+session:
+  set                       show the session settings
+  set mb|tl|t|alg|cost value
+                            default for every problem, e.g. set mb mk5
+  set sim on|off            simulate every result
+  set format steps|net|both print results as steps, as a network or both
+  unset name                drop a setting
+  last                      show the previous result again
+  export file               write the previous result in the network format
+  redo -out 60 60           solve the previous problem again with some flags changed
+  history                   list the problems solved this session
+  !3                        solve problem 3 from the history again
+  help                      show this
+  quit                      leave, as does -q
+editing:
+  left, right, home, end    move along the line, ctrl-a and ctrl-e also go to the ends
+  up, down                  go through the lines entered this session
+  ctrl-c                    drop the line        ctrl-d  leave on an empty line";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Steps,
+    Network,
+    Both,
+}
+
+pub struct Session {
+    // flags put in front of every problem, as (flag, value)
+    settings: Vec<(String, String)>,
+    format: Format,
+    // problem lines in the order they were solved
+    history: Vec<String>,
+    last: Option<(Balancer, BalancerResult)>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            settings: vec![],
+            format: Format::Steps,
+            history: vec![],
+            last: None,
+        }
+    }
+
+    // handles one line of input, returns false once the session should end
+    pub fn handle(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command {
+            "" => {}
+            "help" => println!("{}", HELP),
+            "quit" | "exit" => {
+                println!("Quitting");
+                return false;
+            }
+            "set" if rest.is_empty() => self.show_settings(),
+            "set" => self.set(rest),
+            "unset" => {
+                self.settings.retain(|(flag, _)| flag[1..] != *rest);
+                if rest == "format" {
+                    self.format = Format::Steps;
+                }
+            }
+            "last" => match &self.last {
+                Some((balancer, result)) => self.print_result(balancer, result),
+                None => println!("Nothing solved yet"),
+            },
+            "export" => self.export(rest),
+            "history" => {
+                for (i, problem) in self.history.iter().enumerate() {
+                    println!("{:>3}  {}", i + 1, problem);
+                }
+            }
+            "redo" => match self.history.last() {
+                Some(previous) => {
+                    let problem = redo_line(previous, rest);
+                    return self.solve_line(&problem);
+                }
+                None => println!("Nothing solved yet"),
+            },
+            _ if command.starts_with('!') => {
+                let problem = command[1..]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| self.history.get(i.wrapping_sub(1)))
+                    .cloned();
+                match problem {
+                    Some(problem) => return self.solve_line(&problem),
+                    None => println!("No problem {} in the history", &command[1..]),
+                }
+            }
+            _ => return self.solve_line(line),
+        }
+        true
+    }
+
+    fn set(&mut self, setting: &str) {
+        let (name, value) = setting.split_once(char::is_whitespace).unwrap_or((setting, ""));
+        let value = value.trim();
+
+        match (name, value) {
+            ("format", "steps") => self.format = Format::Steps,
+            ("format", "net") => self.format = Format::Network,
+            ("format", "both") => self.format = Format::Both,
+            ("sim", "on") => self.set_flag("-sim", ""),
+            ("sim", "off") => self.settings.retain(|(flag, _)| flag != "-sim"),
+            ("mb" | "tl" | "t" | "alg" | "cost", value) if !value.is_empty() => self.set_flag(&format!("-{}", name), value),
+            _ => println!("Invalid setting: {}, see help", setting),
+        }
+    }
+
+    fn set_flag(&mut self, flag: &str, value: &str) {
+        match self.settings.iter_mut().find(|(f, _)| f == flag) {
+            Some(setting) => setting.1 = value.to_string(),
+            None => self.settings.push((flag.to_string(), value.to_string())),
+        }
+    }
+
+    fn show_settings(&self) {
+        for (flag, value) in &self.settings {
+            println!("{} {}", &flag[1..], if value.is_empty() { "on" } else { value });
+        }
+        let format = match self.format {
+            Format::Steps => "steps",
+            Format::Network => "net",
+            Format::Both => "both",
+        };
+        println!("format {}", format);
+    }
+
+    fn export(&self, file: &str) {
+        let Some((balancer, result)) = &self.last else {
+            println!("Nothing solved yet");
+            return;
+        };
+        if file.is_empty() {
+            println!("export needs a file name");
+            return;
+        }
+        match balancer.graph(result) {
+            Some(graph) => match std::fs::write(file, format!("{}\n", graph)) {
+                Ok(_) => println!("Saved balancer to {}", file),
+                Err(error) => println!("Could not write {}: {}", file, error),
+            },
+            None => println!("Solution does not form a valid balancer"),
+        }
+    }
+
+    // solves a problem line with the session settings in front of it, flags
+    // on the line itself come later and so win over the settings
+    fn solve_line(&mut self, line: &str) -> bool {
+        let defaults = self.settings
+            .iter()
+            .map(|(flag, value)| format!("{} {}", flag, value))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let args = Args::new(format!("{} {}", defaults, line));
+        // println!("{:?}",args);
+        if args.quit {
+            println!("Quitting");
+            return false;
+        }
+        if args.bench {
+            run_benchmarks();
+            return true;
+        }
+        if let Some(file) = &args.batch {
+            self.history.push(line.to_string());
+            solve_batch(file, &defaults, args.report.as_deref());
+            return true;
+        }
+        if args.network.is_some() || args.load.is_some() {
+            self.history.push(line.to_string());
+            solve_network(&args);
+            return true;
+        }
+        if args.inputs.is_empty() {
+            println!("No input belts given");
+            return true;
+        }
+        if args.outputs.is_empty() {
+            println!("No output belts given");
+            return true;
+        }
+        self.history.push(line.to_string());
+
+        if !args.miners.is_empty() {
+            println!("{}", MinerReport {
+                miners: &args.miners,
+                max_belt: args.max_belt,
+            });
+        }
+        if !args.consumers.is_empty() {
+            println!("{}", ConsumerReport {
+                consumers: &args.consumers,
+                supply: args.inputs.iter().sum(),
+                surplus: args.surplus,
+            });
+        }
+        let simulate = args.simulate;
+        let manifold = args.manifold;
+        let layout = args.layout;
+        let floor = args.floor;
+        let block = args.block.clone();
+        let starve = args.starve.clone();
+        let bursts = args.bursts.clone();
+        let save = args.save.clone();
+        let svg = args.svg.clone();
+        let balancer = Balancer::new(args);
+        let result = balancer.find_ideal_balance();
+
+        self.print_result(&balancer, &result);
+        self.last = Some((balancer, result));
+        let (balancer, result) = self.last.as_ref().unwrap();
+
+        if let Some(file) = save {
+            self.export(&file);
+        }
+        // layouts and drawings place belts, a fluid plan's pipes would come
+        // out as a belt balancer
+        if balancer.fluid && (svg.is_some() || layout || floor.is_some()) {
+            println!("Layouts and drawings are for belts, fluid plans are not laid out or drawn");
+        }
+        let placed = !balancer.fluid && !result.path().is_empty();
+        if let Some(file) = svg.filter(|_| placed) {
+            match balancer.svg(result) {
+                Some(image) => match std::fs::write(&file, format!("{}\n", image)) {
+                    Ok(_) => println!("Drew balancer to {}", file),
+                    Err(error) => println!("Could not write {}: {}", file, error),
+                },
+                None => println!("Solution does not form a valid balancer"),
+            }
+        }
+        if let Some(max) = floor.filter(|_| placed) {
+            match balancer.floor_plan(result, max) {
+                Some(plan) => println!("{}", plan),
+                None => println!("No layout fits on floors of {} x {} foundations", max.0, max.1),
+            }
+        } else if layout && placed {
+            match balancer.layout(result) {
+                Some(layout) => println!("{}", layout),
+                None => println!("No layout found, the belts could not all be routed"),
+            }
+        }
+        if balancer.is_sushi() {
+            if !result.path().is_empty() {
+                println!("{}", balancer.sushi_report(result));
+            }
+            if simulate || manifold || block.is_some() || starve.is_some() || !bursts.is_empty() {
+                println!("The simulator has no item types, sorting plans are not simulated");
+            }
+            return true;
+        }
+        if balancer.fluid {
+            println!("{}", balancer.fluid_report(result));
+            if simulate || manifold || block.is_some() || starve.is_some() || !bursts.is_empty() {
+                println!("The simulator moves items on belts, fluid plans are not simulated");
+            }
+            return true;
+        }
+        if simulate {
+            match balancer.simulate(result) {
+                Some(report) => println!("{}", report),
+                None => println!("Solution does not form a valid balancer"),
+            }
+        }
+        if balancer.saturate {
+            if let Some(report) = balancer.saturation(result) {
+                println!("{}", report);
+            }
+        }
+        if balancer.is_priority() {
+            if let Some(report) = balancer.fill_order(result) {
+                println!("{}", report);
+            }
+        }
+        if balancer.strict {
+            if let Some(report) = balancer.contributions(result) {
+                println!("{}", report);
+            }
+        }
+        if manifold {
+            println!("{}", balancer.manifold(result));
+        }
+        if let Some(block) = block {
+            let scenarios = if block.is_empty() {
+                balancer.single_blockages()
+            } else {
+                vec![balancer.blockage(&block)]
+            };
+            match balancer.resilience(result, &scenarios) {
+                Some(report) => println!("{}", report),
+                None => println!("Solution does not form a valid balancer"),
+            }
+        }
+        if let Some(starve) = starve {
+            let scenarios = if starve.is_empty() {
+                balancer.single_failures()
+            } else {
+                vec![balancer.starved(&starve)]
+            };
+            match balancer.starvation(result, &scenarios) {
+                Some(report) => println!("{}", report),
+                None => println!("Solution does not form a valid balancer"),
+            }
+        }
+        if !bursts.is_empty() {
+            match balancer.burst_sources(&bursts) {
+                Ok(sources) => match balancer.bursts(result, sources) {
+                    Some(report) => println!("{}", report),
+                    None => println!("Solution does not form a valid balancer"),
+                },
+                Err(message) => println!("{}", message),
+            }
+        }
+        true
+    }
+
+    fn print_result(&self, balancer: &Balancer, result: &BalancerResult) {
+        if self.format != Format::Network {
+            println!("{}", result);
+            // the steps only show rates, so labelled belts are named under them
+            let labelled = balancer.input_labels.iter().chain(&balancer.output_labels).any(|l| l.is_some());
+            if labelled && !result.path().is_empty() {
+                println!("inputs: {}", belt_names(&balancer.input_names(), &balancer.inputs));
+                println!("outputs: {}", belt_names(&balancer.output_names(), &balancer.outputs));
+            }
+        }
+        if self.format != Format::Steps {
+            match balancer.graph(result) {
+                Some(graph) => println!("{}", graph),
+                None => println!("Solution does not form a valid balancer"),
+            }
+        }
+    }
+}
+
+// belts by name with their rates, like `copper 240.00, in2 60.00`
+fn belt_names(names: &[String], rates: &[u64]) -> String {
+    names
+        .iter()
+        .zip(rates)
+        .map(|(name, &rate)| format!("{} {:.2}", name, rate as f64 / 1e8))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// splits a problem line into flags with their values, -net keeps the rest of the line
+fn flag_groups(line: &str) -> Vec<String> {
+    let (head, network) = split_network(line);
+
+    let mut groups: Vec<String> = vec![];
+    for word in head.split_whitespace() {
+        match groups.last_mut() {
+            Some(group) if !word.starts_with('-') => {
+                group.push(' ');
+                group.push_str(word);
+            }
+            _ => groups.push(word.to_string()),
+        }
+    }
+    if let Some(network) = network {
+        groups.push(format!("-net {}", network));
+    }
+    groups
+}
+
+// the previous problem with each flag in `changes` replacing the same flag
+pub fn redo_line(previous: &str, changes: &str) -> String {
+    let mut groups = flag_groups(previous);
+    for change in flag_groups(changes) {
+        let flag = change.split_whitespace().next();
+        match groups.iter().position(|g| g.split_whitespace().next() == flag) {
+            Some(i) => groups[i] = change,
+            None => groups.push(change),
+        }
+    }
+    groups.join(" ")
+}
+
+fn solve_batch(file: &str, defaults: &str, report_file: Option<&str>) {
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("Could not read {}: {}", file, error);
+            return;
+        }
+    };
+
+    let report = run_batch(&text, defaults);
+    match report_file {
+        Some(report_file) => match std::fs::write(report_file, format!("{}\n", report)) {
+            Ok(_) => println!("Saved report to {}", report_file),
+            Err(error) => println!("Could not write {}: {}", report_file, error),
+        },
+        None => println!("{}", report),
+    }
+}
+
+fn solve_network(args: &Args) {
+    let text = match &args.load {
+        Some(file) => match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => {
+                println!("Could not read {}: {}", file, error);
+                return;
+            }
+        },
+        None => args.network.clone().unwrap_or_default(),
+    };
+    let graph = match parse_network(&text) {
+        Ok(graph) => graph,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    // -in overrides the rates written on the input belts
+    let supply = if args.inputs.is_empty() {
+        graph
+            .nodes_of(NodeKind::Input)
+            .iter()
+            .map(|&n| graph.outgoing(n).iter().map(|&e| graph.edges[e].rate).sum())
+            .collect::<Vec<u64>>()
+    } else {
+        args.inputs.iter().map(|x| (*x as f64 * 1e8) as u64).collect::<Vec<u64>>()
+    };
+    match solve_flow(&graph, &supply, args.max_belt * 10u64.pow(8)) {
+        Ok(solution) => println!("{}", solution),
+        Err(error) => println!("{}", error),
+    }
+}
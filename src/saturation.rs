@@ -0,0 +1,90 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::NodeKind;
+use crate::sim::Simulator;
+use std::fmt;
+use std::fmt::Formatter;
+
+pub struct SaturationReport {
+    pub input_names: Vec<String>,
+    pub output_names: Vec<String>,
+    // what each input could deliver and what the balancer takes off it
+    pub offered: Vec<u64>,
+    pub taken: Vec<u64>,
+    pub requested: Vec<u64>,
+    pub delivered: Vec<u64>,
+    // belts running at the belt limit, as (from, to)
+    pub full: Vec<(String, String)>,
+    pub max_belt: u64,
+}
+
+impl SaturationReport {
+    // within 1% or 0.1 items/min of the requested rate, whichever is looser
+    pub fn matches(&self) -> bool {
+        self.requested.iter().zip(&self.delivered).all(|(&e, &m)| {
+            e.abs_diff(m) <= (e / 100).max(10_000_000)
+        })
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    // simulate the plan with every input offering its full rate and outputs
+    // taking whatever arrives, so saturated belts back up like in game and the
+    // rates measured are what the design really moves
+    pub fn saturation(&self, result: &BalancerResult) -> Option<SaturationReport> {
+        let graph = self.graph(result)?;
+        let flow = Simulator::new(&graph, self.max_belt).run(600, 6000);
+
+        let taken = graph
+            .nodes_of(NodeKind::Input)
+            .iter()
+            .map(|&n| graph.outgoing(n).iter().map(|&e| flow.edges[e]).sum())
+            .collect();
+        // a belt moving whole items never quite reaches the limit, allow 1%
+        let full = graph.edges
+            .iter()
+            .zip(&flow.edges)
+            .filter(|(_, &rate)| rate >= self.max_belt - self.max_belt / 100)
+            .map(|(edge, _)| (graph.nodes[edge.from].name(), graph.nodes[edge.to].name()))
+            .collect();
+
+        Some(SaturationReport {
+            input_names: self.input_names(),
+            output_names: self.output_names(),
+            offered: self.inputs.clone(),
+            taken,
+            requested: self.outputs.clone(),
+            delivered: flow.outputs,
+            full,
+            max_belt: self.max_belt,
+        })
+    }
+}
+
+impl fmt::Display for SaturationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "effective rates once saturated belts back up:")?;
+        for ((name, offered), taken) in self.input_names.iter().zip(&self.offered).zip(&self.taken) {
+            write!(f, "  {}: {:.2} offered, {:.2} taken", name, *offered as f64 / 1e8, *taken as f64 / 1e8)?;
+            if offered > taken {
+                write!(f, ", {:.2} backs up", (offered - taken) as f64 / 1e8)?;
+            }
+            writeln!(f)?;
+        }
+        for ((name, requested), delivered) in self.output_names.iter().zip(&self.requested).zip(&self.delivered) {
+            writeln!(f, "  {}: {:.2} requested, {:.2} delivered", name, *requested as f64 / 1e8, *delivered as f64 / 1e8)?;
+        }
+        for (from, to) in &self.full {
+            writeln!(f, "  {} -> {} runs full at {:.2}", from, to, self.max_belt as f64 / 1e8)?;
+        }
+
+        let through = self.delivered.iter().sum::<u64>();
+        let offered = self.offered.iter().sum::<u64>();
+        write!(f, "{:.2} of {:.2} offered gets through, ", through as f64 / 1e8, offered as f64 / 1e8)?;
+        if self.matches() {
+            write!(f, "every output gets its rate")
+        } else {
+            write!(f, "outputs do not get the rates the design expects")
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::backpressure::UNCAPPED;
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{BalancerGraph, NodeKind};
+use crate::sim::Simulator;
+use std::fmt;
+use std::fmt::Formatter;
+
+// what the plan is run under: the rate each input delivers and the most each
+// output will consume, None for no limit
+#[derive(Clone)]
+pub struct Conditions {
+    pub supply: Vec<u64>,
+    pub demand: Vec<Option<u64>>,
+}
+
+pub struct ScenarioResult {
+    pub name: String,
+    pub demand: Vec<Option<u64>>,
+    pub before: Vec<u64>,
+    pub after: Vec<u64>,
+    // belts over the belt limit, as (from, to, rate)
+    pub overloaded: Vec<(String, String, u64)>,
+    // input rate that could not get into the balancer
+    pub backed_up: u64,
+}
+
+impl ScenarioResult {
+    // what each output gets short of its rate before, leaving out what the
+    // scenario itself holds it to
+    pub fn shortfalls(&self) -> Vec<u64> {
+        self.before
+            .iter()
+            .zip(&self.after)
+            .zip(&self.demand)
+            .map(|((before, after), limit)| (*before).min(limit.unwrap_or(u64::MAX)).saturating_sub(*after))
+            .collect()
+    }
+
+    // output that loses the most, as (output, shortfall), the first one on a tie
+    pub fn worst(&self) -> Option<(usize, u64)> {
+        self.shortfalls()
+            .into_iter()
+            .enumerate()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+    }
+}
+
+pub struct ScenarioReport {
+    pub max_belt: u64,
+    pub names: Vec<String>,
+    pub scenarios: Vec<ScenarioResult>,
+}
+
+// balancer related functions
+impl Balancer {
+    // simulate the plan under `normal` and then under each scenario with the
+    // belts left uncapped, so a belt a scenario pushes over the limit shows up
+    // as overloaded, and report how the output rates shift from normal
+    pub fn run_scenarios(&self, result: &BalancerResult, normal: Conditions, scenarios: &[Conditions]) -> Option<ScenarioReport> {
+        let graph = self.graph(result)?;
+        let mut sim = Simulator::new(&graph, UNCAPPED);
+        sim.supply = normal.supply.clone();
+        sim.demand = normal.demand.clone();
+        let before = sim.run(600, 6000).outputs;
+        // what even the normal run can't take in, like a saturated surplus
+        let surplus = normal.supply.iter().sum::<u64>().saturating_sub(before.iter().sum());
+
+        let scenarios = scenarios
+            .iter()
+            .map(|conditions| {
+                sim.supply = conditions.supply.clone();
+                sim.demand = conditions.demand.clone();
+                let flow = sim.run(600, 6000);
+
+                let overloaded = graph.edges
+                    .iter()
+                    .zip(&flow.edges)
+                    .filter(|(_, &rate)| rate > self.max_belt)
+                    .map(|(edge, &rate)| (
+                        graph.nodes[edge.from].name(),
+                        graph.nodes[edge.to].name(),
+                        rate
+                    ))
+                    .collect();
+
+                ScenarioResult {
+                    name: scenario_name(&graph, &normal, conditions),
+                    demand: conditions.demand.clone(),
+                    before: before.clone(),
+                    backed_up: conditions.supply
+                        .iter()
+                        .sum::<u64>()
+                        .saturating_sub(flow.outputs.iter().sum())
+                        .saturating_sub(surplus),
+                    after: flow.outputs,
+                    overloaded,
+                }
+            })
+            .collect();
+
+        Some(ScenarioReport {
+            max_belt: self.max_belt,
+            names: self.output_names(),
+            scenarios,
+        })
+    }
+}
+
+// the inputs and outputs a scenario changes from normal
+fn scenario_name(graph: &BalancerGraph, normal: &Conditions, conditions: &Conditions) -> String {
+    let inputs = graph.nodes_of(NodeKind::Input);
+    let outputs = graph.nodes_of(NodeKind::Output);
+    let starved = conditions.supply
+        .iter()
+        .zip(&normal.supply)
+        .enumerate()
+        .filter(|(_, (s, n))| s != n)
+        .map(|(i, (&s, _))| match s {
+            0 => format!("{} stopped", graph.nodes[inputs[i]].name()),
+            rate => format!("{} at {}", graph.nodes[inputs[i]].name(), rate as f64 / 1e8),
+        });
+    let limited = conditions.demand
+        .iter()
+        .zip(&normal.demand)
+        .enumerate()
+        .filter(|(_, (d, n))| d != n)
+        .filter_map(|(i, (limit, _))| match limit {
+            Some(0) => Some(format!("{} blocked", graph.nodes[outputs[i]].name())),
+            Some(rate) => Some(format!("{} at {}", graph.nodes[outputs[i]].name(), *rate as f64 / 1e8)),
+            None => None,
+        });
+    let changes = starved.chain(limited).collect::<Vec<String>>();
+
+    if changes.is_empty() {
+        "everything running normally".to_string()
+    } else {
+        changes.join(", ")
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut dry = 0;
+        for scenario in &self.scenarios {
+            writeln!(f, "{}:", scenario.name)?;
+            let shortfalls = scenario.shortfalls();
+            for (((name, before), after), short) in self.names.iter().zip(&scenario.before).zip(&scenario.after).zip(&shortfalls) {
+                let shift = *after as f64 / 1e8 - *before as f64 / 1e8;
+                write!(f, "  {}: {:.2} -> {:.2} ({:+.2})", name, *before as f64 / 1e8, *after as f64 / 1e8, shift)?;
+                // only outputs the scenario still lets consume can run dry
+                if *after == 0 && *short > 0 {
+                    write!(f, " runs dry")?;
+                    dry += 1;
+                }
+                writeln!(f)?;
+            }
+            for (from, to, rate) in &scenario.overloaded {
+                writeln!(f, "  {} -> {} carries {:.2}, over the {} belt limit",
+                    from, to, *rate as f64 / 1e8, self.max_belt as f64 / 1e8
+                )?;
+            }
+            if scenario.backed_up > 0 {
+                writeln!(f, "  {:.2} backs up into the inputs", scenario.backed_up as f64 / 1e8)?;
+            }
+            if let Some((j, short)) = scenario.worst().filter(|&(_, short)| short > 0) {
+                writeln!(f, "  worst shortfall: {} down {:.2} ({:.0}%)",
+                    self.names[j], short as f64 / 1e8, short as f64 / scenario.before[j] as f64 * 100.0
+                )?;
+            }
+            if scenario.overloaded.is_empty() {
+                writeln!(f, "  degrades gracefully")?;
+            } else {
+                writeln!(f, "  needs a faster belt tier")?;
+            }
+        }
+
+        if dry == 0 {
+            write!(f, "every output keeps some supply in every scenario")
+        } else {
+            write!(f, "an output runs dry {} times across the scenarios", dry)
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{BalancerGraph, NodeKind};
+use std::fmt;
+use std::fmt::Formatter;
+
+// one tick is a second, one item is 60 items/min worth of rate credit
+const TICKS_PER_MIN: u64 = 60;
+const ITEM: u64 = TICKS_PER_MIN * 100_000_000;
+
+// items a belt can hold before it backs up, belts faster than this many
+// items a tick hold one more than they can move in a tick
+const BELT_BUFFER: u64 = 10;
+
+#[derive(Clone, Default)]
+struct Belt {
+    queue: u64,
+    // throughput left this tick, in rate units
+    credit: u64,
+    moved: u64,
+}
+
+// steps items through a balancer graph one tick at a time. splitters hand items
+// to their outputs in turn and mergers take from their inputs in turn, both
+// skipping belts that are full or empty, like the buildings in game
+pub struct Simulator<'a> {
+    graph: &'a BalancerGraph,
+    pub belt_speed: u64,
+    // rate each input node tries to push, in input node order
+    pub supply: Vec<u64>,
+    // most each output node will consume, None for as much as arrives
+    pub demand: Vec<Option<u64>>,
+    // belts into and out of every node, and the input and output nodes in order
+    incoming: Vec<Vec<usize>>,
+    outgoing: Vec<Vec<usize>>,
+    input_nodes: Vec<usize>,
+    output_nodes: Vec<usize>,
+    overflow: Vec<bool>,
+}
+
+// measured rates in items/min (scaled by 1e8), averaged over the measured ticks
+pub struct Flow {
+    pub edges: Vec<u64>,
+    pub outputs: Vec<u64>,
+}
+
+// belts and buildings part way through a run
+struct State {
+    belts: Vec<Belt>,
+    // round robin position and leftover rate credit of every node, credit
+    // never carries more than one item over so a blocked node can't save up
+    next: Vec<usize>,
+    credit: Vec<u64>,
+    // items each output has taken
+    consumed: Vec<u64>,
+    buffer: u64,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(graph: &'a BalancerGraph, belt_speed: u64) -> Simulator<'a> {
+        let supply = graph
+            .nodes_of(NodeKind::Input)
+            .iter()
+            .map(|&n| graph.outgoing(n).iter().map(|&e| graph.edges[e].rate).sum())
+            .collect();
+        let demand = vec![None; graph.nodes_of(NodeKind::Output).len()];
+
+        Simulator {
+            graph,
+            belt_speed,
+            supply,
+            demand,
+            incoming: (0..graph.nodes.len()).map(|n| graph.incoming(n)).collect(),
+            outgoing: (0..graph.nodes.len()).map(|n| graph.outgoing(n)).collect(),
+            input_nodes: graph.nodes_of(NodeKind::Input),
+            output_nodes: graph.nodes_of(NodeKind::Output),
+            overflow: (0..graph.edges.len()).map(|e| graph.overflow.contains(&e)).collect(),
+        }
+    }
+
+    fn start(&self) -> State {
+        State {
+            belts: vec![Belt::default(); self.graph.edges.len()],
+            next: vec![0; self.graph.nodes.len()],
+            credit: vec![0; self.graph.nodes.len()],
+            consumed: vec![0; self.output_nodes.len()],
+            // no belt moves more than the belt speed or everything supplied
+            buffer: BELT_BUFFER.max(self.belt_speed.min(self.supply.iter().sum()).div_ceil(ITEM) + 1),
+        }
+    }
+
+    // runs `warmup` ticks to let the belts fill, then measures over `ticks` more
+    pub fn run(&self, warmup: u64, ticks: u64) -> Flow {
+        let mut state = self.start();
+        for tick in 0..warmup + ticks {
+            if tick == warmup {
+                state.belts.iter_mut().for_each(|b| b.moved = 0);
+                state.consumed.iter_mut().for_each(|x| *x = 0);
+            }
+            self.tick(&mut state);
+        }
+
+        let rate = |count: u64| count * ITEM / ticks.max(1);
+        Flow {
+            edges: state.belts.iter().map(|b| rate(b.moved)).collect(),
+            outputs: state.consumed.into_iter().map(rate).collect(),
+        }
+    }
+
+    // ticks until every output with a demand has taken its rate over the last
+    // `window` ticks, give or take an item, None if that takes over `max_ticks`
+    pub fn fill_time(&self, window: u64, max_ticks: u64) -> Option<u64> {
+        let mut state = self.start();
+        let mut history = vec![];
+        for tick in 1..=max_ticks {
+            self.tick(&mut state);
+            history.push(state.consumed.clone());
+            if tick < window {
+                continue;
+            }
+
+            let before = if tick == window { None } else { history.get((tick - window - 1) as usize) };
+            let full = self.demand.iter().enumerate().all(|(i, demand)| {
+                let taken = state.consumed[i] - before.map_or(0, |b| b[i]);
+                demand.is_none_or(|rate| taken + 1 >= rate * window / ITEM)
+            });
+            if full {
+                return Some(tick);
+            }
+        }
+        None
+    }
+
+    fn tick(&self, state: &mut State) {
+        let State { belts, next, credit, consumed, buffer } = state;
+        let accepts = |belt: &Belt| belt.queue < *buffer && belt.credit >= ITEM;
+
+        for belt in belts.iter_mut() {
+            belt.credit = (belt.credit + self.belt_speed).min(self.belt_speed + ITEM);
+        }
+
+        for (node, kind) in self.graph.nodes.iter().map(|n| n.kind).enumerate() {
+            let (ins, outs) = (&self.incoming[node], &self.outgoing[node]);
+            match kind {
+                NodeKind::Input => {
+                    let i = self.input_nodes.iter().position(|&n| n == node).unwrap();
+                    credit[node] = (credit[node] + self.supply[i]).min(self.supply[i] + ITEM);
+                    while credit[node] >= ITEM {
+                        let Some(&out) = outs.iter().find(|&&e| accepts(&belts[e])) else { break };
+                        push(&mut belts[out]);
+                        credit[node] -= ITEM;
+                    }
+                }
+                NodeKind::Output => {
+                    let i = self.output_nodes.iter().position(|&n| n == node).unwrap();
+                    let limit = match self.demand[i] {
+                        Some(rate) => {
+                            credit[node] = (credit[node] + rate).min(rate + ITEM);
+                            credit[node] / ITEM
+                        }
+                        None => u64::MAX,
+                    };
+                    let mut taken = 0;
+                    for &e in ins {
+                        let n = belts[e].queue.min(limit - taken);
+                        belts[e].queue -= n;
+                        taken += n;
+                    }
+                    if self.demand[i].is_some() {
+                        credit[node] -= taken * ITEM;
+                    }
+                    consumed[i] += taken;
+                }
+                // smart splitters only use their overflow belts once the others
+                // are full. items have no type here, sorting is not simulated
+                NodeKind::Filter if outs.iter().any(|&e| self.overflow[e]) => {
+                    while let Some(&from) = ins.iter().find(|&&e| belts[e].queue > 0) {
+                        let k = round_robin(outs, next[node], |e| !self.overflow[e] && accepts(&belts[e]))
+                            .or_else(|| outs.iter().position(|&e| self.overflow[e] && accepts(&belts[e])));
+                        let Some(k) = k else { break };
+                        belts[from].queue -= 1;
+                        push(&mut belts[outs[k]]);
+                        if !self.overflow[outs[k]] {
+                            next[node] = k + 1;
+                        }
+                    }
+                }
+                NodeKind::Splitter | NodeKind::Filter => {
+                    while let Some(&from) = ins.iter().find(|&&e| belts[e].queue > 0) {
+                        let Some(k) = round_robin(outs, next[node], |e| accepts(&belts[e])) else { break };
+                        belts[from].queue -= 1;
+                        push(&mut belts[outs[k]]);
+                        next[node] = k + 1;
+                    }
+                }
+                NodeKind::Merger => {
+                    while let Some(&to) = outs.iter().find(|&&e| accepts(&belts[e])) {
+                        let Some(k) = round_robin(ins, next[node], |e| belts[e].queue > 0) else { break };
+                        belts[ins[k]].queue -= 1;
+                        push(&mut belts[to]);
+                        next[node] = k + 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn push(belt: &mut Belt) {
+    belt.queue += 1;
+    belt.credit -= ITEM;
+    belt.moved += 1;
+}
+
+// first of `edges` from `start` onwards, wrapping around, that passes `ok`
+fn round_robin(edges: &[usize], start: usize, ok: impl Fn(usize) -> bool) -> Option<usize> {
+    (0..edges.len())
+        .map(|i| (start + i) % edges.len())
+        .find(|&k| ok(edges[k]))
+}
+
+pub struct SimulationReport {
+    pub names: Vec<String>,
+    pub expected: Vec<u64>,
+    pub measured: Vec<u64>,
+    // highest rate seen on any belt
+    pub busiest: u64,
+}
+
+impl SimulationReport {
+    // within 1% or 0.1 items/min of the requested rate, whichever is looser
+    pub fn matches(&self) -> bool {
+        self.expected.iter().zip(&self.measured).all(|(&e, &m)| {
+            e.abs_diff(m) <= (e / 100).max(10_000_000)
+        })
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "simulated steady state:")?;
+        for ((name, e), m) in self.names.iter().zip(&self.expected).zip(&self.measured) {
+            writeln!(f, "  {}: {:.2} requested, {:.2} simulated", name, *e as f64 / 1e8, *m as f64 / 1e8)?;
+        }
+        writeln!(f, "  busiest belt: {:.2}", self.busiest as f64 / 1e8)?;
+        if self.matches() {
+            write!(f, "all outputs match")
+        } else {
+            write!(f, "outputs do not match the request")
+        }
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    // simulate the solved balancer and compare what reaches each output with the request
+    pub fn simulate(&self, result: &BalancerResult) -> Option<SimulationReport> {
+        let graph = self.graph(result)?;
+        let mut sim = Simulator::new(&graph, self.max_belt);
+        // overflow belts only take what outputs leave, so outputs have to stop at their rate
+        if self.is_priority() {
+            sim.demand = self.outputs.iter().map(|&x| Some(x)).collect();
+        }
+        let flow = sim.run(600, 6000);
+
+        Some(SimulationReport {
+            names: self.output_names(),
+            expected: self.outputs.clone(),
+            busiest: flow.edges.iter().copied().max().unwrap_or(0),
+            measured: flow.outputs,
+        })
+    }
+}
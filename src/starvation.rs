@@ -0,0 +1,38 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::scenario::{Conditions, ScenarioReport};
+
+// balancer related functions
+impl Balancer {
+    // simulate each scenario, where a scenario gives the rate each input still
+    // delivers, with outputs consuming no more than they asked for
+    pub fn starvation(&self, result: &BalancerResult, scenarios: &[Vec<u64>]) -> Option<ScenarioReport> {
+        let normal = Conditions { supply: self.inputs.clone(), demand: self.outputs.iter().map(|&x| Some(x)).collect() };
+        let scenarios = scenarios
+            .iter()
+            .map(|supply| Conditions { supply: supply.clone(), ..normal.clone() })
+            .collect::<Vec<Conditions>>();
+        self.run_scenarios(result, normal, &scenarios)
+    }
+
+    // a scenario from (input number, rate) pairs, inputs not listed keep their rate
+    pub fn starved(&self, limits: &[(usize, f32)]) -> Vec<u64> {
+        let mut supply = self.inputs.clone();
+        for &(input, rate) in limits {
+            if input >= 1 && input <= supply.len() {
+                supply[input - 1] = ((rate as f64 * 1e8) as u64).min(supply[input - 1]);
+            }
+        }
+        supply
+    }
+
+    // every input stopped on its own, one scenario each
+    pub fn single_failures(&self) -> Vec<Vec<u64>> {
+        (0..self.inputs.len())
+            .map(|i| {
+                let mut supply = self.inputs.clone();
+                supply[i] = 0;
+                supply
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,188 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::graph::{BalancerGraph, Builder, NodeKind};
+use crate::rational::Rational;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Instant;
+
+// most mixed belts the inputs are spread over before giving up
+const MAX_MIXED: u64 = 64;
+
+// the mixed belts a strict balancer starts its search from: k equal belts that
+// each carry 1/k of every input. k has to be made from 2 and 3 way splits,
+// divide every input and leave each mixed belt under the belt limit
+fn mixed_belts(inputs: &[u64], max_belt: u64) -> Option<u64> {
+    let total = inputs.iter().sum::<u64>();
+    (1..=MAX_MIXED)
+        .filter(|&k| split_factors(k).is_some())
+        .find(|&k| total / k <= max_belt && inputs.iter().all(|x| x.is_multiple_of(k)))
+}
+
+// k as the 2 and 3 way splits that divide a belt into k equal parts
+fn split_factors(mut k: u64) -> Option<Vec<u64>> {
+    let mut factors = vec![];
+    for f in [3, 2] {
+        while k.is_multiple_of(f) {
+            factors.push(f);
+            k /= f;
+        }
+    }
+    (k == 1).then_some(factors)
+}
+
+// balancer related functions
+impl Balancer {
+    // a balancer where every output takes the same share of every input. the
+    // inputs are first split into k parts and one part of each merged into k
+    // fully mixed belts, anything built from those is mixed as well, so the
+    // normal search takes it from there
+    pub fn find_strict_balance(&self, start: Instant) -> BalancerResult {
+        let mut rest = self.clone();
+        rest.strict = false;
+        if self.inputs.len() == 1 {
+            return rest.find_ideal_balance_since(start);
+        }
+
+        let Some(k) = mixed_belts(&self.inputs, self.max_belt) else {
+            println!("No way to mix the inputs onto belts under the belt limit");
+            return BalancerResult::default();
+        };
+        let factors = split_factors(k).unwrap_or_default();
+
+        let mut builder = Builder::new(&self.inputs);
+        let mut parts = vec![];
+        for &rate in &self.inputs {
+            let mut belts = vec![(builder.graph.add_node(NodeKind::Input), rate)];
+            for &ways in &factors {
+                belts = belts.into_iter().flat_map(|belt| builder.split(belt, ways)).collect();
+            }
+            parts.push(belts);
+        }
+
+        let mut mixed = vec![];
+        for g in 0..k as usize {
+            let mut belts = parts.iter().map(|belts| belts[g]).collect::<Vec<_>>();
+            while belts.len() > 1 {
+                let merged = builder.merge(&belts[..belts.len().min(3)]);
+                belts.drain(..belts.len().min(3));
+                belts.insert(0, merged);
+            }
+            mixed.push(belts[0]);
+        }
+
+        rest.inputs = mixed.iter().map(|&(_, rate)| rate).collect();
+        let result = rest.find_ideal_balance_since(start);
+        let Builder { mut graph, mut path, .. } = builder;
+        if graph.lay_out(mixed, &self.outputs, result.path()).is_none() {
+            return BalancerResult::default();
+        }
+        path.extend(result.path().iter().cloned());
+
+        let time = start.elapsed().as_secs_f64();
+        let strict = if result.is_canonical() {
+            BalancerResult::canonical(path, time)
+        } else {
+            BalancerResult::new(path, result.total_states(), result.checked_states(), time)
+        };
+        strict.with_graph(graph)
+    }
+
+    // the share of every input each output ends up with, from a solved graph
+    pub fn contributions(&self, result: &BalancerResult) -> Option<ContributionReport> {
+        let graph = self.graph(result)?;
+        Some(ContributionReport {
+            matrix: contribution_matrix(&graph),
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            input_names: self.input_names(),
+            output_names: self.output_names(),
+        })
+    }
+}
+
+// rate of each input that reaches each output, as matrix[input][output]. nodes
+// have to come in an order where every belt leads to a later node, as they do
+// in graphs built from a path
+pub fn contribution_matrix(graph: &BalancerGraph) -> Vec<Vec<Rational>> {
+    let inputs = graph.nodes_of(NodeKind::Input);
+    let outputs = graph.nodes_of(NodeKind::Output);
+    // what each belt carries of each input
+    let mut carried = vec![vec![Rational::zero(); inputs.len()]; graph.edges.len()];
+
+    for node in 0..graph.nodes.len() {
+        let incoming = graph.incoming(node);
+        let outgoing = graph.outgoing(node);
+        let mut mix = vec![Rational::zero(); inputs.len()];
+        match inputs.iter().position(|&n| n == node) {
+            Some(i) => {
+                mix[i] = outgoing.iter().map(|&e| Rational::from_scaled(graph.edges[e].rate)).fold(Rational::zero(), |a, b| a + b);
+            }
+            None => {
+                for &e in &incoming {
+                    for (m, &c) in mix.iter_mut().zip(&carried[e]) {
+                        *m = *m + c;
+                    }
+                }
+            }
+        }
+
+        let total = mix.iter().fold(Rational::zero(), |a, &b| a + b);
+        for &e in &outgoing {
+            let share = Rational::from_scaled(graph.edges[e].rate);
+            carried[e] = mix.iter().map(|&m| if total.is_zero() { m } else { m * share / total }).collect();
+        }
+    }
+
+    (0..inputs.len())
+        .map(|i| outputs
+            .iter()
+            .map(|&o| graph.incoming(o).iter().fold(Rational::zero(), |a, &e| a + carried[e][i]))
+            .collect())
+        .collect()
+}
+
+pub struct ContributionReport {
+    pub matrix: Vec<Vec<Rational>>,
+    pub inputs: Vec<u64>,
+    pub outputs: Vec<u64>,
+    pub input_names: Vec<String>,
+    pub output_names: Vec<String>,
+}
+
+impl ContributionReport {
+    // what a fully mixed balancer gives, each output takes its share of every input
+    pub fn expected(&self, input: usize, output: usize) -> Rational {
+        let total = self.inputs.iter().sum::<u64>();
+        Rational::from_scaled(self.inputs[input]) * Rational::from_scaled(self.outputs[output]) / Rational::from_scaled(total)
+    }
+
+    pub fn is_strict(&self) -> bool {
+        (0..self.inputs.len()).all(|i| (0..self.outputs.len()).all(|o| self.matrix[i][o] == self.expected(i, o)))
+    }
+}
+
+impl fmt::Display for ContributionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "items/min each output takes from each input:")?;
+        let width = self.input_names.iter().map(|name| name.len() + 1).max().unwrap_or(0).max(6);
+        let column = self.output_names.iter().map(|name| name.len() + 1).max().unwrap_or(0).max(10);
+        write!(f, "{:>width$}", "")?;
+        for name in &self.output_names {
+            write!(f, "{:>column$}", name)?;
+        }
+        writeln!(f)?;
+        for (name, row) in self.input_names.iter().zip(&self.matrix) {
+            write!(f, "{:>width$}", name)?;
+            for rate in row {
+                write!(f, "{:>column$.3}", rate.to_f64())?;
+            }
+            writeln!(f)?;
+        }
+
+        if self.is_strict() {
+            write!(f, "every output takes the same share of every input")
+        } else {
+            write!(f, "outputs take uneven shares of the inputs, a starved input hits some outputs harder")
+        }
+    }
+}
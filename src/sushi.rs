@@ -0,0 +1,255 @@
+use crate::balancer::{Balancer, BalancerResult, BalancerState};
+use crate::graph::{BalancerGraph, Builder, Edge, NodeKind};
+use crate::helpers::multiset;
+use crate::operation::Operation;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Instant;
+
+// sides of a programmable splitter, in the order its outputs are listed
+const SIDES: [&str; 3] = ["left", "centre", "right"];
+
+// every item on the belts, in the order they first show up
+fn item_names(belts: &[Vec<(String, u64)>]) -> Vec<String> {
+    let mut items: Vec<String> = vec![];
+    for (item, _) in belts.iter().flatten() {
+        if !items.contains(item) {
+            items.push(item.clone());
+        }
+    }
+    items
+}
+
+impl Builder {
+    // programmable splitters taking every item off a mixed belt onto a belt of
+    // its own, as (item, from node, rate). a splitter sorts up to three items,
+    // with more it takes two and sends the rest on through an "any" output
+    fn sort(&mut self, (mut from, mut rate): (usize, u64), mix: &[(String, u64)]) -> Vec<(String, usize, u64)> {
+        let mut sorted = vec![];
+        let mut left = mix;
+        loop {
+            let filter = self.graph.add_node(NodeKind::Filter);
+            self.graph.edges.push(Edge { from, to: filter, rate });
+
+            let taken = if left.len() <= 3 { left.len() } else { 2 };
+            let mut parts = left[..taken].iter().map(|(_, rate)| *rate).collect::<Vec<u64>>();
+            let mut items = left[..taken].iter().map(|(item, _)| item.clone()).collect::<Vec<String>>();
+            sorted.extend(left[..taken].iter().map(|(item, rate)| (item.clone(), filter, *rate)));
+            left = &left[taken..];
+            if !left.is_empty() {
+                parts.push(left.iter().map(|(_, rate)| rate).sum());
+                items.push("any".to_string());
+            }
+
+            self.push(Operation::Filter {
+                input: rate,
+                output: (parts.first().copied(), parts.get(1).copied(), parts.get(2).copied()),
+                items,
+            });
+            if left.is_empty() {
+                return sorted;
+            }
+            (from, rate) = (filter, parts[2]);
+        }
+    }
+}
+
+// lists the belts out of every programmable splitter in the order of its
+// sides, which is how the plan says to set it. `tags` names the item on each
+// belt, filters come in the same order as the filter steps of the path
+fn order_sides(graph: &mut BalancerGraph, tags: &[Option<String>], path: &[(Operation, BalancerState)]) {
+    let settings = path.iter().filter_map(|(op, _)| match op {
+        Operation::Filter { items, .. } => Some(items),
+        _ => None,
+    });
+
+    for (filter, items) in graph.nodes_of(NodeKind::Filter).into_iter().zip(settings) {
+        let slots = graph.outgoing(filter);
+        let mut sorted = slots.clone();
+        sorted.sort_by_key(|&e| items.iter().position(|item| tags[e].as_ref() == Some(item)));
+        let belts = sorted.iter().map(|&e| graph.edges[e].clone()).collect::<Vec<Edge>>();
+        for (slot, belt) in slots.into_iter().zip(belts) {
+            graph.edges[slot] = belt;
+        }
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    pub fn is_sushi(&self) -> bool {
+        self.input_items.iter().chain(&self.output_items).any(|mix| !mix.is_empty())
+    }
+
+    // every belt has to say what it carries, belts of several items have to
+    // fit on a belt, and each item has to leave at the rate it comes in
+    fn check_items(&self) -> Result<(), String> {
+        let named = |items: &[Vec<(String, u64)>], count: usize| items.len() == count && items.iter().all(|mix| !mix.is_empty());
+        if !named(&self.input_items, self.inputs.len()) || !named(&self.output_items, self.outputs.len()) {
+            return Err("Every belt needs its items when any belt has them, like iron:60+copper:30".to_string());
+        }
+
+        // mixed inputs come in on one belt and mixed outputs are merged onto one
+        let mixed = self.input_names()
+            .into_iter()
+            .zip(self.inputs.iter().zip(&self.input_items))
+            .chain(self.output_names().into_iter().zip(self.outputs.iter().zip(&self.output_items)));
+        for (name, (&rate, mix)) in mixed {
+            if mix.len() > 1 && rate > self.max_belt {
+                return Err(format!("{} carries {} mixed, over the {} belt limit", name, rate as f64 / 1e8, self.max_belt as f64 / 1e8));
+            }
+        }
+
+        for item in item_names(&self.input_items).iter().chain(&item_names(&self.output_items)) {
+            let total = |belts: &[Vec<(String, u64)>]| belts
+                .iter()
+                .flatten()
+                .filter(|(name, _)| name == item)
+                .map(|(_, rate)| rate)
+                .sum::<u64>();
+            let (supply, demand) = (total(&self.input_items), total(&self.output_items));
+            if supply != demand {
+                return Err(format!("Unbalanced {}: {} in != {} out", item, supply as f64 / 1e8, demand as f64 / 1e8));
+            }
+        }
+        Ok(())
+    }
+
+    // a balancer for belts of several items. mixed inputs are sorted onto a
+    // belt per item by programmable splitters, each item is then balanced on
+    // its own by the normal search, and outputs asking for several items get
+    // their parts merged back together
+    pub fn find_sushi_balance(&self, start: Instant) -> BalancerResult {
+        if let Err(message) = self.check_items() {
+            println!("{}", message);
+            return BalancerResult::default();
+        }
+
+        let mut builder = Builder::new(&self.inputs);
+        let mut belts = vec![];
+        for (&rate, mix) in self.inputs.iter().zip(&self.input_items) {
+            let input = builder.graph.add_node(NodeKind::Input);
+            match mix.as_slice() {
+                [(item, _)] => belts.push((item.clone(), input, rate)),
+                mix => belts.extend(builder.sort((input, rate), mix)),
+            }
+        }
+        // the item each belt carries, so far only belts between splitters
+        // come out of a programmable splitter and they carry the rest
+        let mut tags = builder.graph.edges
+            .iter()
+            .map(|e| (builder.graph.nodes[e.from].kind == NodeKind::Filter).then(|| "any".to_string()))
+            .collect::<Vec<Option<String>>>();
+
+        let mut rest = self.clone();
+        rest.input_items = vec![];
+        rest.output_items = vec![];
+        let (mut total, mut checked, mut searched) = (0, 0, false);
+        // belts of each item once balanced, as (item, from node, rate)
+        let mut ends = vec![];
+        for item in item_names(&self.input_items) {
+            let open = belts
+                .iter()
+                .filter(|(name, _, _)| *name == item)
+                .map(|&(_, from, rate)| (from, rate))
+                .collect::<Vec<(usize, u64)>>();
+            rest.inputs = open.iter().map(|&(_, rate)| rate).collect();
+            rest.outputs = self.output_items
+                .iter()
+                .flatten()
+                .filter(|(name, _)| *name == item)
+                .map(|&(_, rate)| rate)
+                .collect();
+
+            let mut path = vec![];
+            if multiset(rest.inputs.clone()) != multiset(rest.outputs.clone()) {
+                let result = rest.find_ideal_balance_since(start);
+                if result.path().is_empty() {
+                    println!("No balancer found for {}", item);
+                    return BalancerResult::default();
+                }
+                searched |= !result.is_canonical();
+                total += result.total_states();
+                checked += result.checked_states();
+                path = result.path().to_vec();
+            }
+
+            let Some(open) = builder.graph.lay_out_path(open, &path) else {
+                return BalancerResult::default();
+            };
+            for (op, _) in path {
+                builder.push(op);
+            }
+            tags.resize(builder.graph.edges.len(), Some(item.clone()));
+            ends.extend(open.into_iter().map(|(from, rate)| (item.clone(), from, rate)));
+        }
+
+        for mix in &self.output_items {
+            let mut parts = vec![];
+            let mut items = vec![];
+            for (item, rate) in mix {
+                let Some(i) = ends.iter().position(|(name, _, x)| name == item && x == rate) else {
+                    return BalancerResult::default();
+                };
+                let (_, from, rate) = ends.remove(i);
+                parts.push((from, rate));
+                items.push(Some(item.clone()));
+            }
+            while parts.len() > 1 {
+                let merged = builder.merge(&parts[..parts.len().min(3)]);
+                parts.drain(..parts.len().min(3));
+                tags.extend(items.drain(..items.len().min(3)));
+                parts.insert(0, merged);
+                items.insert(0, None);
+            }
+
+            let (from, rate) = parts[0];
+            let output = builder.graph.add_node(NodeKind::Output);
+            builder.graph.edges.push(Edge { from, to: output, rate });
+            tags.push(items.remove(0));
+        }
+
+        let Builder { mut graph, path, .. } = builder;
+        order_sides(&mut graph, &tags, &path);
+        let time = start.elapsed().as_secs_f64();
+        let result = if searched {
+            BalancerResult::new(path, total, checked, time)
+        } else {
+            BalancerResult::canonical(path, time)
+        };
+        result.with_graph(graph)
+    }
+
+    // how to set each programmable splitter of a solved plan
+    pub fn sushi_report(&self, result: &BalancerResult) -> SushiReport {
+        let filters = result
+            .iter()
+            .filter_map(|(op, _)| match op {
+                Operation::Filter { items, .. } => Some(items.clone()),
+                _ => None,
+            })
+            .collect();
+
+        SushiReport { filters }
+    }
+}
+
+pub struct SushiReport {
+    // what each side of every programmable splitter takes, in order F1, F2...
+    pub filters: Vec<Vec<String>>,
+}
+
+impl fmt::Display for SushiReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "programmable splitters:")?;
+        for (i, items) in self.filters.iter().enumerate() {
+            let sides = SIDES
+                .iter()
+                .zip(items)
+                .map(|(side, item)| format!("{} {}", side, item))
+                .collect::<Vec<String>>();
+            writeln!(f, "  F{}: {}", i + 1, sides.join(", "))?;
+        }
+        write!(f, "  a sorted item that backs up stops its splitter and everything behind it, \
+            every item has to be drawn or given an overflow")
+    }
+}
@@ -0,0 +1,267 @@
+use crate::balancer::{Balancer, BalancerResult};
+use crate::cmd::BELT_TIERS;
+use crate::graph::{BalancerGraph, NodeKind};
+use crate::layout::columns;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+
+// sizes in pixels
+const LAYER_GAP: f64 = 150.0;
+const ROW_GAP: f64 = 64.0;
+const NODE_WIDTH: f64 = 72.0;
+const NODE_HEIGHT: f64 = 36.0;
+const PADDING: f64 = 40.0;
+// passes of reordering the layers against their neighbours
+const SWEEPS: usize = 8;
+// colour of each tier of BELT_TIERS, then of belts faster than any tier
+const TIER_COLOURS: [&str; 7] = ["#9e9e9e", "#8bc34a", "#03a9f4", "#3f51b5", "#9c27b0", "#ff9800", "#f44336"];
+
+// something taking up a place in a layer, a building or a belt passing
+// through on its way to a later layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Vertex {
+    Node(usize),
+    Bend(usize, usize),
+}
+
+// a layered drawing of a graph, every belt only goes to the next layer with
+// bends standing in for the layers a long belt passes
+pub struct Diagram<'a> {
+    graph: &'a BalancerGraph,
+    // top to bottom within each layer, inputs in the first and outputs in the last
+    pub layers: Vec<Vec<Vertex>>,
+    // the vertices each belt runs through, by edge
+    pub chains: Vec<Vec<Vertex>>,
+}
+
+// the tier a belt of this rate needs, past the end of BELT_TIERS if no belt is fast enough
+pub fn tier(rate: u64) -> usize {
+    BELT_TIERS.iter().position(|&(_, speed)| rate <= speed * 100_000_000).unwrap_or(BELT_TIERS.len())
+}
+
+// a rate to at most 2 decimals without trailing zeros
+fn rate_text(rate: u64) -> String {
+    let text = format!("{:.2}", rate as f64 / 1e8);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl<'a> Diagram<'a> {
+    // layers from the longest path to each building, with the order in each
+    // layer swept back and forth by barycenter to cut down on crossings.
+    // None if the belts go round in a loop
+    pub fn new(graph: &'a BalancerGraph) -> Option<Diagram<'a>> {
+        let columns = columns(graph)?;
+        let count = columns.iter().copied().max().unwrap_or(0) + 1;
+        let mut layers = vec![vec![]; count];
+        for (n, &column) in columns.iter().enumerate() {
+            layers[column].push(Vertex::Node(n));
+        }
+        let chains = graph.edges
+            .iter()
+            .enumerate()
+            .map(|(e, edge)| {
+                let mut chain = vec![Vertex::Node(edge.from)];
+                let passed = columns[edge.from] + 1..columns[edge.to];
+                for (column, layer) in layers.iter_mut().enumerate().take(passed.end).skip(passed.start) {
+                    layer.push(Vertex::Bend(e, column));
+                    chain.push(Vertex::Bend(e, column));
+                }
+                chain.push(Vertex::Node(edge.to));
+                chain
+            })
+            .collect();
+
+        let mut diagram = Diagram { graph, layers, chains };
+        let mut best = (diagram.crossings(), diagram.layers.clone());
+        for sweep in 0..SWEEPS {
+            diagram.reorder(sweep % 2 == 0);
+            let crossings = diagram.crossings();
+            if crossings < best.0 {
+                best = (crossings, diagram.layers.clone());
+            }
+        }
+        diagram.layers = best.1;
+        Some(diagram)
+    }
+
+    // pairs of vertices joined by a piece of belt, each from one layer to the next
+    fn segments(&self) -> impl Iterator<Item = (Vertex, Vertex)> + '_ {
+        self.chains.iter().flat_map(|chain| chain.windows(2).map(|w| (w[0], w[1])))
+    }
+
+    fn places(&self) -> HashMap<Vertex, usize> {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.iter().enumerate().map(|(i, &v)| (v, i)))
+            .collect()
+    }
+
+    // sorts every layer by the average place of its neighbours in the layer
+    // before it, or after it when going back up
+    fn reorder(&mut self, forward: bool) {
+        let segments = self.segments().collect::<Vec<(Vertex, Vertex)>>();
+        let order = match forward {
+            true => (1..self.layers.len()).collect::<Vec<usize>>(),
+            false => (0..self.layers.len().saturating_sub(1)).rev().collect(),
+        };
+        for l in order {
+            let places = self.places();
+            let keys = self.layers[l]
+                .iter()
+                .map(|v| {
+                    let neighbours = segments
+                        .iter()
+                        .filter_map(|&(a, b)| match forward {
+                            true if b == *v => Some(places[&a]),
+                            false if a == *v => Some(places[&b]),
+                            _ => None,
+                        })
+                        .collect::<Vec<usize>>();
+                    match neighbours.is_empty() {
+                        true => places[v] as f64,
+                        false => neighbours.iter().sum::<usize>() as f64 / neighbours.len() as f64,
+                    }
+                })
+                .collect::<Vec<f64>>();
+            let mut keyed = self.layers[l].iter().copied().zip(keys).collect::<Vec<(Vertex, f64)>>();
+            keyed.sort_by(|a, b| a.1.total_cmp(&b.1));
+            self.layers[l] = keyed.into_iter().map(|(v, _)| v).collect();
+        }
+    }
+
+    // pieces of belt that cross another between the same two layers
+    pub fn crossings(&self) -> usize {
+        let places = self.places();
+        let segments = self.segments().map(|(a, b)| (places[&a], places[&b], a)).collect::<Vec<_>>();
+        let layer = |v: Vertex| self.layers.iter().position(|layer| layer.contains(&v));
+        let mut count = 0;
+        for (i, &(a1, b1, v1)) in segments.iter().enumerate() {
+            for &(a2, b2, v2) in &segments[i + 1..] {
+                if layer(v1) == layer(v2) && ((a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2)) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    // centre of every vertex, layers are centred on the tallest one
+    fn centres(&self) -> HashMap<Vertex, (f64, f64)> {
+        let tallest = self.layers.iter().map(|layer| layer.len()).max().unwrap_or(0);
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(l, layer)| {
+                let top = PADDING + NODE_HEIGHT / 2.0 + (tallest - layer.len()) as f64 * ROW_GAP / 2.0;
+                layer.iter().enumerate().map(move |(i, &v)| {
+                    (v, (PADDING + NODE_WIDTH / 2.0 + l as f64 * LAYER_GAP, top + i as f64 * ROW_GAP))
+                })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagram<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let centres = self.centres();
+        let tallest = self.layers.iter().map(|layer| layer.len()).max().unwrap_or(0);
+        let width = PADDING * 2.0 + NODE_WIDTH + (self.layers.len().max(1) - 1) as f64 * LAYER_GAP;
+        let tiers = {
+            let mut tiers = self.graph.edges.iter().map(|e| tier(e.rate)).collect::<Vec<usize>>();
+            tiers.sort();
+            tiers.dedup();
+            tiers
+        };
+        let legend = PADDING + NODE_HEIGHT + (tallest.max(1) - 1) as f64 * ROW_GAP + 20.0;
+        let height = legend + tiers.len() as f64 * 20.0 + PADDING / 2.0;
+
+        writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#, w = width, h = height)?;
+        writeln!(f, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+
+        // belts first so buildings sit on top of their ends
+        for (e, chain) in self.chains.iter().enumerate() {
+            let edge = &self.graph.edges[e];
+            let mut points = chain.iter().map(|v| centres[v]).collect::<Vec<(f64, f64)>>();
+            let last = points.len() - 1;
+            points[0].0 += NODE_WIDTH / 2.0;
+            points[last].0 -= NODE_WIDTH / 2.0;
+
+            let curves = points
+                .windows(2)
+                .map(|w| {
+                    let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+                    let bend = (x1 - x0) / 2.0;
+                    format!(" C {} {} {} {} {} {}", x0 + bend, y0, x1 - bend, y1, x1, y1)
+                })
+                .collect::<String>();
+            let d = format!("M {} {}{}", points[0].0, points[0].1, curves);
+            let dashes = match self.graph.overflow.contains(&e) {
+                true => r#" stroke-dasharray="6 4""#,
+                false => "",
+            };
+            writeln!(f, r#"<path d="{}" fill="none" stroke="{}" stroke-width="3"{}/>"#, d, TIER_COLOURS[tier(edge.rate)], dashes)?;
+            if edge.rate > 0 {
+                let (x, y) = ((points[0].0 + points[1].0) / 2.0, (points[0].1 + points[1].1) / 2.0 - 5.0);
+                writeln!(f, r#"<text x="{}" y="{}" text-anchor="middle" stroke="white" stroke-width="3" paint-order="stroke">{}</text>"#, x, y, rate_text(edge.rate))?;
+            }
+        }
+
+        for (n, node) in self.graph.nodes.iter().enumerate() {
+            let (x, y) = centres[&Vertex::Node(n)];
+            let fill = match node.kind {
+                NodeKind::Input => "#c8e6c9",
+                NodeKind::Output => "#bbdefb",
+                NodeKind::Splitter => "#ffe0b2",
+                NodeKind::Merger => "#e1bee7",
+                NodeKind::Filter => "#fff9c4",
+            };
+            writeln!(f, r##"<rect x="{}" y="{}" width="{}" height="{}" rx="6" fill="{}" stroke="#424242"/>"##,
+                x - NODE_WIDTH / 2.0, y - NODE_HEIGHT / 2.0, NODE_WIDTH, NODE_HEIGHT, fill
+            )?;
+            // inputs and outputs show the rate they carry under their name
+            let rate = match node.kind {
+                NodeKind::Input => Some(self.graph.outgoing(n).iter().map(|&e| self.graph.edges[e].rate).sum::<u64>()),
+                NodeKind::Output => Some(self.graph.incoming(n).iter().map(|&e| self.graph.edges[e].rate).sum::<u64>()),
+                _ => None,
+            };
+            match rate.filter(|&rate| rate > 0) {
+                Some(rate) => {
+                    writeln!(f, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, x, y - 3.0, escape(&node.name()))?;
+                    writeln!(f, r#"<text x="{}" y="{}" text-anchor="middle" font-size="10">{}/min</text>"#, x, y + 11.0, rate_text(rate))?;
+                }
+                None => {
+                    writeln!(f, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, x, y + 4.0, escape(&node.name()))?;
+                }
+            }
+        }
+
+        for (i, &t) in tiers.iter().enumerate() {
+            let y = legend + i as f64 * 20.0;
+            let name = match BELT_TIERS.get(t) {
+                Some((name, speed)) => format!("{} belt, up to {}/min", name, speed),
+                None => "faster than any belt".to_string(),
+            };
+            writeln!(f, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="3"/>"#, PADDING, y, PADDING + 30.0, y, TIER_COLOURS[t])?;
+            writeln!(f, r#"<text x="{}" y="{}">{}</text>"#, PADDING + 40.0, y + 4.0, name)?;
+        }
+        write!(f, "</svg>")
+    }
+}
+
+impl BalancerGraph {
+    pub fn to_svg(&self) -> Option<String> {
+        Some(Diagram::new(self)?.to_string())
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    pub fn svg(&self, result: &BalancerResult) -> Option<String> {
+        self.graph(result)?.to_svg()
+    }
+}
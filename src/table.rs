@@ -0,0 +1,290 @@
+use crate::balancer::{Balancer, BalancerResult, BalancerState};
+use crate::helpers::multiset;
+use crate::operation::Operation;
+use std::time::Instant;
+use Step::{Merge, Split};
+
+// a single building of a canonical balancer, with rates given in units
+// of the owning entry (all inputs together carry `total` units)
+pub enum Step {
+    // split a belt of the given rate 2 or 3 ways
+    Split(u64, u64),
+    Merge(&'static [u64]),
+}
+
+pub struct Canonical {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub total: u64,
+    pub steps: &'static [Step],
+}
+
+impl Canonical {
+    pub fn find(inputs: usize, outputs: usize) -> Option<&'static Canonical> {
+        CANONICAL.iter().find(|c| c.inputs == inputs && c.outputs == outputs)
+    }
+
+    // replay the steps with every unit worth `scale`, returns None if a step
+    // uses a rate that is not on any belt at that point
+    pub fn path(&self, scale: u64) -> Option<Vec<(Operation, BalancerState)>> {
+        let mut state = vec![self.total / self.inputs as u64 * scale; self.inputs];
+        let mut path = vec![];
+
+        for step in self.steps {
+            let (consumed, produced, op) = match step {
+                Split(n, ways) => {
+                    let part = n / ways * scale;
+                    let third = if *ways == 3 { Some(part) } else { None };
+                    let op = Operation::Split {
+                        input: n * scale,
+                        output: (Some(part), Some(part), third),
+                    };
+                    (vec![n * scale], vec![part; *ways as usize], op)
+                }
+                Merge(vals) => {
+                    let vals = vals.iter().map(|x| x * scale).collect::<Vec<u64>>();
+                    let merged = vals.iter().sum::<u64>();
+                    let op = Operation::Merge {
+                        input: (vals.first().copied(), vals.get(1).copied(), vals.get(2).copied()),
+                        output: merged,
+                    };
+                    (vals, vec![merged], op)
+                }
+            };
+
+            for val in consumed {
+                let i = state.iter().position(|&x| x == val)?;
+                state.remove(i);
+            }
+            state.extend(produced);
+            path.push((op, multiset(state.clone())));
+        }
+
+        Some(path)
+    }
+}
+
+// balancer related functions
+impl Balancer {
+    // look up an equal rate request in the canonical table,
+    // returns None when the search has to be used instead
+    pub fn canonical(&self) -> Option<BalancerResult> {
+        let start = Instant::now();
+        let input = *self.inputs.first()?;
+        let output = *self.outputs.first()?;
+        if self.inputs.iter().any(|&x| x != input) || self.outputs.iter().any(|&x| x != output) {
+            return None;
+        }
+
+        let entry = Canonical::find(self.inputs.len(), self.outputs.len())?;
+        let total = self.inputs.iter().sum::<u64>();
+        if total % entry.total != 0 {
+            return None;
+        }
+
+        let path = entry.path(total / entry.total)?;
+        let fits = path.iter().all(|(_, state)| state.iter().all(|&x| x <= self.max_belt));
+        let last = path.last().map_or(multiset(self.inputs.clone()), |(_, state)| state.clone());
+        if !fits || last != multiset(self.outputs.clone()) {
+            return None;
+        }
+
+        Some(BalancerResult::canonical(path, start.elapsed().as_secs_f64()))
+    }
+}
+
+// even n -> m balancers, found by an exhaustive search with no belt
+// carrying more than the larger of the input and output rates. splitters
+// only ever divide by 2 or 3, so without a loop feeding belts back an even
+// split needs m / gcd(n, m) to have no other prime factor: 1 -> 5, 7, 10
+// and 11 can't be built in this model and are left to the search, which
+// can't build them either. n -> n is the inputs passing straight through,
+// -strict mixes them
+pub static CANONICAL: &[Canonical] = &[
+    Canonical {
+        inputs: 1, outputs: 2, total: 2,
+        steps: &[
+            Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 1, outputs: 3, total: 3,
+        steps: &[
+            Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 1, outputs: 4, total: 4,
+        steps: &[
+            Split(4, 2), Split(2, 2), Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 1, outputs: 6, total: 6,
+        steps: &[
+            Split(6, 2), Split(3, 3), Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 1, outputs: 8, total: 8,
+        steps: &[
+            Split(8, 2), Split(4, 2), Split(2, 2), Split(2, 2), Split(4, 2),
+            Split(2, 2), Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 1, outputs: 9, total: 9,
+        steps: &[
+            Split(9, 3), Split(3, 3), Split(3, 3), Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 1, outputs: 12, total: 12,
+        steps: &[
+            Split(12, 2), Split(6, 2), Split(3, 3), Split(3, 3), Split(6, 2),
+            Split(3, 3), Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 1, total: 2,
+        steps: &[
+            Merge(&[1, 1]),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 3, total: 6,
+        steps: &[
+            Split(3, 3), Split(3, 3), Merge(&[1, 1]), Merge(&[1, 1]), Merge(&[1, 1]),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 4, total: 4,
+        steps: &[
+            Split(2, 2), Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 6, total: 6,
+        steps: &[
+            Split(3, 3), Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 8, total: 8,
+        steps: &[
+            Split(4, 2), Split(2, 2), Split(2, 2), Split(4, 2), Split(2, 2),
+            Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 9, total: 18,
+        steps: &[
+            Split(9, 3), Split(9, 3), Merge(&[3, 3]), Split(6, 3), Merge(&[3, 3]),
+            Split(6, 3), Merge(&[3, 3]), Split(6, 3),
+        ],
+    },
+    Canonical {
+        inputs: 2, outputs: 12, total: 12,
+        steps: &[
+            Split(6, 2), Split(3, 3), Split(3, 3), Split(6, 2), Split(3, 3),
+            Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 1, total: 3,
+        steps: &[
+            Merge(&[1, 1, 1]),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 2, total: 6,
+        steps: &[
+            Split(2, 2), Merge(&[1, 2]), Merge(&[1, 2]),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 4, total: 12,
+        steps: &[
+            Split(4, 2), Split(2, 2), Split(2, 2), Split(4, 2), Split(4, 2),
+            Merge(&[1, 2]), Merge(&[1, 2]), Merge(&[1, 2]), Merge(&[1, 2]),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 6, total: 6,
+        steps: &[
+            Split(2, 2), Split(2, 2), Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 8, total: 24,
+        steps: &[
+            Split(8, 2), Split(8, 2), Split(8, 2), Split(4, 2), Split(4, 2),
+            Merge(&[2, 4]), Split(6, 2), Merge(&[2, 4]), Split(6, 2), Merge(&[2, 4]),
+            Split(6, 2), Merge(&[2, 4]), Split(6, 2),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 9, total: 9,
+        steps: &[
+            Split(3, 3), Split(3, 3), Split(3, 3),
+        ],
+    },
+    Canonical {
+        inputs: 3, outputs: 12, total: 12,
+        steps: &[
+            Split(4, 2), Split(2, 2), Split(2, 2), Split(4, 2), Split(2, 2),
+            Split(2, 2), Split(4, 2), Split(2, 2), Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 1, total: 4,
+        steps: &[
+            Merge(&[1, 1]), Merge(&[1, 1, 2]),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 2, total: 4,
+        steps: &[
+            Merge(&[1, 1]), Merge(&[1, 1]),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 3, total: 12,
+        steps: &[
+            Split(3, 3), Merge(&[1, 3]), Merge(&[1, 3]), Merge(&[1, 3]),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 4, total: 4,
+        steps: &[],
+    },
+    Canonical {
+        inputs: 4, outputs: 6, total: 12,
+        steps: &[
+            Split(3, 3), Split(3, 3), Split(3, 3), Split(3, 3), Merge(&[1, 1]),
+            Merge(&[1, 1]), Merge(&[1, 1]), Merge(&[1, 1]), Merge(&[1, 1]),
+            Merge(&[1, 1]),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 8, total: 8,
+        steps: &[
+            Split(2, 2), Split(2, 2), Split(2, 2), Split(2, 2),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 9, total: 36,
+        steps: &[
+            Split(9, 3), Split(9, 3), Split(9, 3), Split(9, 3), Split(3, 3),
+            Split(3, 3), Split(3, 3), Merge(&[1, 3]), Merge(&[1, 3]), Merge(&[1, 3]),
+            Merge(&[1, 3]), Merge(&[1, 3]), Merge(&[1, 3]), Merge(&[1, 3]),
+            Merge(&[1, 3]), Merge(&[1, 3]),
+        ],
+    },
+    Canonical {
+        inputs: 4, outputs: 12, total: 12,
+        steps: &[
+            Split(3, 3), Split(3, 3), Split(3, 3), Split(3, 3),
+        ],
+    },
+];
@@ -1,4 +1,23 @@
-use crate::helpers::{gcd, gcd_vec};
+use crate::balancer::{Balancer, BalancerResult};
+use crate::batch::{run_batch, Status};
+use crate::cmd::{Args, CostModel};
+use crate::editor::Editor;
+use crate::flow::solve_flow;
+use crate::graph::{BalancerGraph, NodeKind};
+use crate::helpers::{gcd, gcd_vec, multiset, Combinations};
+use crate::intern::StateArena;
+use crate::layout::Cell;
+use crate::machines::{Consumer, Miner};
+use crate::manifold::manifold_graph;
+use crate::network::parse_network;
+use crate::operation::Operation;
+use crate::rational::Rational;
+use crate::repl::{redo_line, Session};
+use crate::sim::Simulator;
+use crate::svg::{tier, Diagram};
+use crate::table::{Canonical, CANONICAL};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_gcd() {
@@ -12,4 +31,648 @@ fn test_gcd_vec() {
     let expected = 10;
     let gcd = gcd_vec(vec![270, 270, 260]);
     assert_eq!(expected, gcd);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_canonical_table() {
+    for entry in CANONICAL {
+        let path = entry.path(1).expect("step uses a missing rate");
+        let inputs = vec![entry.total / entry.inputs as u64; entry.inputs];
+        let expected = vec![entry.total / entry.outputs as u64; entry.outputs];
+        let last = path.last().map_or(multiset(inputs), |(_, state)| state.clone());
+        assert_eq!(last, multiset(expected), "{} -> {}", entry.inputs, entry.outputs);
+    }
+
+    // every even split that can be built without loops is in the table,
+    // the ones needing a split by 5, 7 or 11 aren't
+    let smooth = |mut x: u64| {
+        while x.is_multiple_of(2) {
+            x /= 2;
+        }
+        while x.is_multiple_of(3) {
+            x /= 3;
+        }
+        x == 1
+    };
+    let listed = (2..=12).map(|m| (1, m)).chain([(2, 3), (3, 4), (4, 4)]);
+    for (n, m) in listed {
+        let buildable = smooth(m as u64 / gcd(n as u64, m as u64));
+        assert_eq!(Canonical::find(n, m).is_some(), buildable, "{} -> {}", n, m);
+    }
+    let balancer = Balancer::new(Args::new("-in 60 60 60 60 -out 60 60 60 60".to_string()));
+    assert!(balancer.canonical().unwrap().path().is_empty());
+}
+
+#[test]
+fn test_canonical_lookup() {
+    let balancer = Balancer::new(Args::new("-in 120 -out 40 40 40 -mb 1200".to_string()));
+    assert_eq!(balancer.canonical().unwrap().iter().count(), 1);
+
+    let balancer = Balancer::new(Args::new("-in 120 -out 60 30 30 -mb 1200".to_string()));
+    assert!(balancer.canonical().is_none());
+}
+
+#[test]
+fn test_parallel_search() {
+    let balancer = Balancer::new(Args::new("-in 120 -out 60 30 20 10 -t 4".to_string()));
+    let result = balancer.find_ideal_balance();
+    assert!(result.iter().count() > 0);
+
+    // the pool finds as short a plan as the single thread search
+    let problem = "-in 600 600 -out 100 200 300 150 250 200 -tl 10";
+    let single = Balancer::new(Args::new(problem.to_string())).find_ideal_balance();
+    let parallel = Balancer::new(Args::new(format!("{} -t 4", problem))).find_ideal_balance();
+    assert!(parallel.total_states() > 50);
+    assert_eq!(parallel.path().len(), single.path().len());
+    assert_eq!(parallel.path().last().unwrap().1, single.path().last().unwrap().1);
+}
+
+#[test]
+fn test_ida_search() {
+    let astar = Balancer::new(Args::new("-in 120 -out 60 30 20 10 -alg astar".to_string()));
+    let ida = Balancer::new(Args::new("-in 120 -out 60 30 20 10 -alg ida".to_string()));
+    let ida_len = ida.find_ideal_balance().iter().count();
+    assert!(ida_len > 0);
+    assert!(ida_len <= astar.find_ideal_balance().iter().count());
+}
+
+#[test]
+fn test_cost_models() {
+    assert_eq!(Args::new("-in 120 -out 60 60".to_string()).cost_model, CostModel::Buildings);
+    assert_eq!(Args::new("-in 120 -out 60 60 -cost splitters".to_string()).cost_model, CostModel::Splitters);
+    assert_eq!(Args::new("-in 120 -out 60 60 -cost MERGERS".to_string()).cost_model, CostModel::Mergers);
+
+    let problem = "-in 90 30 -out 40 40 40 -mb 1200";
+    let cost = |result: &BalancerResult, model| result.path().iter().map(|(op, _)| op.cost(model)).sum::<i64>();
+    let fewest = Balancer::new(Args::new(problem.to_string())).find_ideal_balance();
+    for (flag, model) in [("splitters", CostModel::Splitters), ("mergers", CostModel::Mergers)] {
+        let result = Balancer::new(Args::new(format!("{} -cost {}", problem, flag))).find_ideal_balance();
+        assert!(!result.path().is_empty());
+        // never worse under its own model than the plan with the fewest buildings
+        assert!(cost(&result, model) <= cost(&fewest, model));
+        assert!(result.path().len() >= fewest.path().len());
+    }
+}
+
+#[test]
+fn test_line_editor() {
+    let mut editor = Editor::new();
+    // left steps back over the c, backspace drops the b
+    assert_eq!(editor.edit_keys(b"abc\x1b[DX\n"), "abXc\n");
+    assert_eq!(editor.edit_keys(b"xy\x7fz\x01-\n"), "-xz\n");
+    // up recalls the last line first, down comes back to the one being typed
+    assert_eq!(editor.edit_keys(b"\x1b[A\x1b[A!\n"), "abXc!\n");
+    assert_eq!(editor.edit_keys(b"new\x1b[A\x1b[B\n"), "new\n");
+    // ctrl-c drops the line, ctrl-d on an empty line ends the input
+    assert_eq!(editor.edit_keys(b"junk\x03ok\n"), "ok\n");
+    assert_eq!(editor.edit_keys(b"\x04"), "");
+    assert_eq!(editor.edit_keys(b"last"), "last\n");
+}
+
+#[test]
+fn test_state_arena() {
+    let mut arena = StateArena::new(10);
+    let small = multiset(vec![30, 10, 20]);
+    let large = multiset((1..=20).map(|x| x * 10).collect());
+
+    let small_id = arena.intern(&small);
+    let large_id = arena.intern(&large);
+    assert_eq!(arena.intern(&multiset(vec![10, 20, 30])), small_id);
+    assert_eq!(arena.get(small_id), small);
+    assert_eq!(arena.get(large_id), large);
+    assert_eq!(arena.len(), 2);
+
+    // states are only kept once, every one is still found again by its id
+    let many = (1..500).map(|x| multiset(vec![x * 10, 20])).collect::<Vec<_>>();
+    let ids = many.iter().map(|state| arena.intern(state)).collect::<Vec<_>>();
+    for (state, id) in many.iter().zip(ids) {
+        assert_eq!(arena.intern(state), id);
+        assert_eq!(&arena.get(id), state);
+    }
+}
+
+#[test]
+fn test_combinations_skip_duplicates() {
+    let pairs = Combinations::new(&[1, 2, 2, 3], 2).collect::<Vec<_>>();
+    assert_eq!(pairs, vec![
+        (Some(1), Some(2), None),
+        (Some(1), Some(3), None),
+        (Some(2), Some(2), None),
+        (Some(2), Some(3), None),
+    ]);
+
+    let triples = Combinations::new(&[1, 1, 1, 2], 3).collect::<Vec<_>>();
+    assert_eq!(triples, vec![(Some(1), Some(1), Some(1)), (Some(1), Some(1), Some(2))]);
+}
+
+#[test]
+fn test_simulate_canonical_table() {
+    // 10 items/min for every unit of the entry
+    let scale = 10 * 100_000_000;
+    for entry in CANONICAL {
+        let inputs = vec![entry.total / entry.inputs as u64 * scale; entry.inputs];
+        let outputs = vec![entry.total / entry.outputs as u64 * scale; entry.outputs];
+        let graph = BalancerGraph::new(&inputs, &outputs, &entry.path(scale).unwrap()).unwrap();
+        let flow = Simulator::new(&graph, 1200 * 100_000_000).run(600, 6000);
+        for (&expected, &measured) in outputs.iter().zip(&flow.outputs) {
+            assert!(expected.abs_diff(measured) <= expected / 100);
+        }
+    }
+}
+
+#[test]
+fn test_simulate_search_results() {
+    for problem in ["-in 120 -out 60 30 20 10", "-in 480 -out 100 100 100 90 90", "-in 240 60 -out 100 100 50 50"] {
+        let balancer = Balancer::new(Args::new(problem.to_string() + " -mb 1200"));
+        let result = balancer.find_ideal_balance();
+        assert!(balancer.simulate(&result).unwrap().matches());
+    }
+}
+
+#[test]
+fn test_simulate_catches_truncated_split() {
+    // a split of 10 written down as three belts of 3 each
+    let balancer = Balancer::new(Args::new("-in 10 -out 3 3 3 -mb 1200".to_string()));
+    let op = Operation::Split {
+        input: 10 * 100_000_000,
+        output: (Some(3 * 100_000_000), Some(3 * 100_000_000), Some(3 * 100_000_000)),
+    };
+    let result = BalancerResult::new(vec![(op, multiset(balancer.outputs.clone()))], 0, 0, 0.);
+    assert!(!balancer.simulate(&result).unwrap().matches());
+}
+
+#[test]
+fn test_blocked_output_redistributes() {
+    let balancer = Balancer::new(Args::new("-in 120 -out 60 60 -mb 1200".to_string()));
+    let result = balancer.find_ideal_balance();
+    let report = balancer.resilience(&result, &[balancer.blockage(&[(1, 0.0)])]).unwrap();
+    let scenario = &report.scenarios[0];
+    assert_eq!(scenario.after[0], 0);
+    assert_eq!(scenario.after[1], 120 * 100_000_000);
+    assert_eq!(scenario.backed_up, 0);
+    assert!(scenario.overloaded.is_empty());
+}
+
+#[test]
+fn test_solve_flow_with_loop() {
+    // a 3 way splitter feeding one output back into the merger in front of it
+    let mut graph = BalancerGraph::default();
+    let belts = [("in1", "M1"), ("M1", "S1"), ("S1", "out1"), ("S1", "out2"), ("S1", "M1")];
+    for (from, to) in belts {
+        let (from, to) = (graph.node_by_name(from).unwrap(), graph.node_by_name(to).unwrap());
+        graph.connect(from, to);
+    }
+
+    let solution = solve_flow(&graph, &[100 * 100_000_000], 120 * 100_000_000).unwrap();
+    assert_eq!(solution.rates[1], Rational::new(150, 1));
+    assert_eq!(solution.rates[2], Rational::new(50, 1));
+    assert_eq!(solution.rates[4], Rational::new(50, 1));
+    assert_eq!(solution.over, vec![1]);
+}
+
+#[test]
+fn test_network_round_trip() {
+    let balancer = Balancer::new(Args::new("-in 100 -out 25 75 -mb 1200".to_string()));
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+
+    let text = graph.to_string();
+    let parsed = parse_network(&text).unwrap();
+    assert_eq!(parsed.to_string(), text);
+    // parsing orders belts by where they start, so compare them as sets
+    let belts = |g: &crate::graph::BalancerGraph| {
+        let mut belts = g.edges
+            .iter()
+            .map(|e| (g.nodes[e.from].name(), g.nodes[e.to].name(), e.rate))
+            .collect::<Vec<_>>();
+        belts.sort();
+        belts
+    };
+    assert_eq!(belts(&parsed), belts(&graph));
+}
+
+#[test]
+fn test_labelled_belts() {
+    let args = Args::new("-in copper=240 -out smelt_a=60 bus=120 smelt_b=60".to_string());
+    assert_eq!(args.output_labels[2], Some("smelt_b".to_string()));
+    let balancer = Balancer::new(args);
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+
+    // outputs of equal rate keep the order their labels were given in
+    let names = graph.nodes_of(NodeKind::Output).iter().map(|&n| graph.nodes[n].name()).collect::<Vec<_>>();
+    assert_eq!(names, ["smelt_a", "bus", "smelt_b"]);
+    let report = balancer.simulate(&result).unwrap().to_string();
+    assert!(report.contains("bus: 120.00 requested"));
+
+    let parsed = parse_network(&graph.to_string()).unwrap();
+    assert_eq!(parsed.to_string(), graph.to_string());
+    assert!(parse_network("in1:copper -> S1:left").is_err());
+
+    // repeats number their labels, a label names one belt only
+    let args = Args::new("-in 60x2 -out a=40x3 a=0 in1=20".to_string());
+    assert_eq!(args.output_labels, [Some("a_1".to_string()), Some("a_2".to_string()), Some("a_3".to_string()), None]);
+}
+
+#[test]
+fn test_network_parse_errors() {
+    let graph = parse_network("# splitter\nin1 -> S1 (90); S1 -> out1 (45), out2\n").unwrap();
+    assert_eq!(graph.edges.len(), 3);
+    assert_eq!(graph.edges[1].rate, 45 * 100_000_000);
+    assert_eq!(graph.edges[2].rate, 0);
+
+    let error = parse_network("in1 -> S1\nS1 -> out1 out2").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 12));
+    let error = parse_network("in1 -> S1\n  X1 -> out1").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 3));
+    let error = parse_network("in1 -> S1 (fast)").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 12));
+}
+
+#[test]
+fn test_redo_line() {
+    let previous = "-in 120 -out 40 40 40 -mb 780 -sim";
+    assert_eq!(redo_line(previous, "-out 60 60"), "-in 120 -out 60 60 -mb 780 -sim");
+    assert_eq!(redo_line(previous, "-tl 5 -mb mk6"), "-in 120 -out 40 40 40 -mb mk6 -sim -tl 5");
+    assert_eq!(redo_line("-in 60 -net in1 -> S1", "-in 90"), "-in 90 -net in1 -> S1");
+
+    // -net inside a path isn't the network flag
+    let line = "-in 120 -out 60 60 -svg /tmp/my-network.svg -save plans/iron-net.txt";
+    let args = Args::new(line.to_string());
+    assert_eq!(args.svg, Some("/tmp/my-network.svg".to_string()));
+    assert_eq!(args.save, Some("plans/iron-net.txt".to_string()));
+    assert_eq!(args.network, None);
+    assert_eq!(redo_line(line, "-in 240"), "-in 240 -out 60 60 -svg /tmp/my-network.svg -save plans/iron-net.txt");
+    let args = Args::new("-svg my-network.svg -net in1 -> out1".to_string());
+    assert_eq!(args.svg, Some("my-network.svg".to_string()));
+    assert_eq!(args.network, Some("in1 -> out1".to_string()));
+}
+
+#[test]
+fn test_batch_report() {
+    let text = "\
+# belts for the iron line
+-in 120 -out 40 40 40
+-in 60 -out 25 25
+-in 120 -out 40 40 40 -mb 1200
+-in 120 -out 40 40 40 -mb 120
+
+-net in1 -> out1
+";
+    let report = run_batch(text, "-tl 5");
+    let entries = &report.entries;
+    assert_eq!(entries.len(), 5);
+    assert_eq!((entries[0].line, &entries[0].status, entries[0].buildings), (2, &Status::Solved, 1));
+    assert_eq!(entries[1].status, Status::Unbalanced);
+    assert!(entries[2].cached);
+    // a different belt tier is a different problem
+    assert!(!entries[3].cached);
+    assert!(matches!(entries[4].status, Status::Invalid(_)));
+
+    // so is another time limit or algorithm, they can change what the search finds
+    let report = run_batch("-in 120 -out 40 40 40\n-in 120 -out 40 40 40 -tl 1\n-in 120 -out 40 40 40 -alg ida\n", "-tl 5");
+    let cached = report.entries.iter().map(|e| e.cached).collect::<Vec<_>>();
+    assert_eq!(cached, [false, false, false]);
+    let report = run_batch("-in 120 -out 40 40 40 -alg ida\n-in 120 -out 40 40 40 -alg ida\n", "-tl 5");
+    assert!(report.entries[1].cached);
+
+    // a surplus is only unbalanced without -saturate, and saturated plans aren't shared
+    let report = run_batch("-in 600 600 -out 390 390 -mb 780 -saturate\n-in 600 600 -out 390 390 -mb 780\n", "-tl 5");
+    let entries = &report.entries;
+    assert_eq!(entries[0].status, Status::Solved);
+    assert!(entries[0].buildings > 0);
+    assert_eq!(entries[1].status, Status::Unbalanced);
+    let report = run_batch("-in 600 600 -out 390 390 -mb 780 -saturate\n-in 600 600 -out 390 390 -mb 780 -saturate\n", "-tl 5");
+    assert!(!report.entries[1].cached);
+}
+
+#[test]
+fn test_consumer_outputs() {
+    let smelters = Consumer::parse("2xsmelter@30:150%").unwrap();
+    assert_eq!((smelters.count, smelters.rate()), (2, 45.0));
+    assert!(Consumer::parse("constructor@15").is_some());
+    assert!(Consumer::parse("2xsmelter@30:300%").is_none());
+    assert!(Consumer::parse("0xsmelter@30").is_none());
+
+    let args = Args::new("-in 240 -consumers 3xconstructor@30 1xassembler@45 -mb 1200".to_string());
+    assert_eq!(args.outputs, vec![30.0, 30.0, 30.0, 45.0, 105.0]);
+    assert_eq!(args.surplus, 105.0);
+
+    let args = Args::new("-in 60 -out 10 -consumers 2xconstructor@30 -mb 1200".to_string());
+    assert_eq!(args.outputs, vec![10.0, 30.0, 30.0]);
+    assert_eq!(args.surplus, -10.0);
+}
+
+#[test]
+fn test_miner_inputs() {
+    assert_eq!(Miner::parse("mk1@impure").unwrap().rate(), 30.0);
+    assert_eq!(Miner::parse("2xmk2@pure:150%").unwrap().rate(), 360.0);
+    assert!(Miner::parse("mk4@pure").is_none());
+    assert!(Miner::parse("mk2@rich").is_none());
+
+    // a pure mk3 at 250% makes 1200/min, more than a mk5 belt carries
+    let args = Args::new("-miners 2xmk2@pure mk3@pure:250% -out 240 240 780 -mb mk5".to_string());
+    assert_eq!(args.inputs, vec![240.0, 240.0, 780.0]);
+}
+
+#[test]
+fn test_fluid_search() {
+    // belts need a 3 way split and a merge, one uneven junction will do
+    let balancer = Balancer::new(Args::new("-in 600 -out 200 400 -fluid".to_string()));
+    assert_eq!(balancer.max_belt, 600 * 100_000_000);
+    let result = balancer.find_ideal_balance();
+    assert_eq!(result.path().len(), 1);
+    assert_eq!(balancer.fluid_report(&result).uneven.len(), 1);
+
+    // 240 fits in an mk1 pipe of 300, so an even three way split is one junction
+    let balancer = Balancer::new(Args::new("-in 240 -out 80 80 80 -fluid mk1 -t 2".to_string()));
+    let result = balancer.find_ideal_balance();
+    assert!(matches!(result.path()[0].0, Operation::Split { .. }));
+    assert!(balancer.fluid_report(&result).uneven.is_empty());
+
+    // pipes aren't drawn as belts, the same plan on belts is
+    let dir = std::env::temp_dir();
+    let pipes = dir.join("fluid_search_pipes.svg");
+    let belts = dir.join("fluid_search_belts.svg");
+    let mut session = Session::new();
+    assert!(session.handle(&format!("-in 240 -out 80 80 80 -fluid mk1 -layout -svg {}", pipes.display())));
+    assert!(session.handle(&format!("-in 240 -out 80 80 80 -svg {}", belts.display())));
+    assert!(!pipes.exists());
+    assert!(std::fs::remove_file(&belts).is_ok());
+}
+
+#[test]
+fn test_manifold() {
+    let graph = manifold_graph(&[60, 60], &[30, 30, 30, 10, 10, 10]);
+    assert_eq!(graph.nodes_of(NodeKind::Merger).len(), 1);
+    assert_eq!(graph.nodes_of(NodeKind::Splitter).len(), 3);
+    assert_eq!(graph.nodes_of(NodeKind::Output).len(), 6);
+
+    let balancer = Balancer::new(Args::new("-in 120 -out 30 30 30 10 10 10 -mb 1200".to_string()));
+    let result = balancer.find_ideal_balance();
+    let report = balancer.manifold(&result);
+    assert!(report.matches());
+    assert!(report.manifold_fill.is_some());
+    assert_eq!(report.manifold_buildings, 3);
+}
+
+#[test]
+fn test_strict_balance() {
+    // 900 doesn't fit on one mk5 belt, so each input is split in two and
+    // mixed onto two 450 belts
+    let balancer = Balancer::new(Args::new("-in 600 300 -out 450 450 -mb 780 -strict".to_string()));
+    let result = balancer.find_ideal_balance();
+    let report = balancer.contributions(&result).unwrap();
+    assert!(report.is_strict());
+
+    let balancer = Balancer::new(Args::new("-in 120 60 -out 90 90 -mb 1200 -strict".to_string()));
+    let result = balancer.find_ideal_balance();
+    assert!(balancer.contributions(&result).unwrap().is_strict());
+
+    // the search after mixing counts its time limit from the start of the whole design
+    let balancer = Balancer::new(Args::new("-in 120 60 -out 100 80 -mb 1200 -strict -tl 1".to_string()));
+    assert!(!balancer.find_ideal_balance().path().is_empty());
+    let result = balancer.find_ideal_balance_since(Instant::now() - Duration::from_secs(2));
+    assert!(result.path().is_empty());
+
+    // the plain search just passes the inputs through when it can
+    let balancer = Balancer::new(Args::new("-in 60 60 -out 60 60 -mb 1200".to_string()));
+    let result = balancer.find_ideal_balance();
+    assert!(!balancer.contributions(&result).unwrap().is_strict());
+}
+
+#[test]
+fn test_input_starvation() {
+    // passing the inputs straight through leaves out1 dry when in1 stops,
+    // a strict balancer shares the loss between both outputs
+    let balancer = Balancer::new(Args::new("-in 60 60 -out 60 60 -mb 1200".to_string()));
+    let result = balancer.find_ideal_balance();
+    let report = balancer.starvation(&result, &balancer.single_failures()).unwrap();
+    assert!(report.scenarios[0].after.contains(&0));
+
+    let balancer = Balancer::new(Args::new("-in 60 60 -out 60 60 -mb 1200 -strict".to_string()));
+    let result = balancer.find_ideal_balance();
+    let report = balancer.starvation(&result, &[balancer.starved(&[(1, 0.0)])]).unwrap();
+    let (_, short) = report.scenarios[0].worst().unwrap();
+    assert!(short.abs_diff(30 * 100_000_000) <= 100_000_000);
+}
+
+#[test]
+fn test_sushi_belts() {
+    let args = Args::new("-in iron:60+copper:30+wire:30+screw:60 -out iron:30 iron:30+copper:30 wire:30+screw:60".to_string());
+    let balancer = Balancer::new(args);
+    assert!(balancer.is_sushi());
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+    let rates = graph.nodes_of(NodeKind::Output).iter().map(|&n| graph.edges[graph.incoming(n)[0]].rate / 100_000_000).collect::<Vec<_>>();
+    assert_eq!(rates, [30, 60, 90]);
+    // four items take two programmable splitters, the first passes wire and screw on
+    assert_eq!(balancer.sushi_report(&result).filters, [vec!["iron", "copper", "any"], vec!["wire", "screw"]]);
+
+    // belts out of a programmable splitter follow its sides, iron on the left
+    let balancer = Balancer::new(Args::new("-in iron:30+copper:30 -out copper:30 iron:30".to_string()));
+    let graph = balancer.graph(&balancer.find_ideal_balance()).unwrap();
+    assert_eq!(graph.to_string(), "in1 -> F1 (60)\nF1 -> out2 (30), out1 (30)");
+
+    let balancer = Balancer::new(Args::new("-in iron:60 copper:60 -out iron:90 copper:30".to_string()));
+    assert!(balancer.find_ideal_balance().path().is_empty());
+
+    // a mixed belt's rate is what its items add up to once scaled
+    let balancer = Balancer::new(Args::new("-in iron:0.1+copper:0.2 -out iron:0.1 copper:0.2".to_string()));
+    assert_eq!(balancer.inputs[0], balancer.input_items[0].iter().map(|(_, rate)| rate).sum::<u64>());
+    assert!(!balancer.find_ideal_balance().path().is_empty());
+
+    // mixed belts have to fit on one belt, in and out
+    let balancer = Balancer::new(Args::new("-in iron:400+copper:400 -out iron:400 copper:400 -mb 780".to_string()));
+    assert!(balancer.find_ideal_balance().path().is_empty());
+    let balancer = Balancer::new(Args::new("-in iron:400 copper:400 -out iron:400+copper:400 -mb 780".to_string()));
+    assert!(balancer.find_ideal_balance().path().is_empty());
+    let balancer = Balancer::new(Args::new("-in iron:300+copper:300 -out iron:300 copper:300 -mb 780".to_string()));
+    assert!(!balancer.find_ideal_balance().path().is_empty());
+}
+
+#[test]
+fn test_priority_fill_order() {
+    let balancer = Balancer::new(Args::new("-in 240 -out a=60!1 b=60!1 c=40!2 d=80".to_string()));
+    assert!(balancer.is_priority());
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+    assert_eq!(graph.overflow.len(), 2);
+    assert!(balancer.simulate(&result).unwrap().matches());
+
+    let report = balancer.fill_order(&result).unwrap();
+    assert!(report.scenarios.iter().all(|s| s.holds()));
+    // at half supply the first group is fed in full and nothing is left over
+    assert_eq!(report.scenarios[2].expected, [60, 60, 0, 0].map(|x| x * 100_000_000));
+
+    let parsed = parse_network(&graph.to_string()).unwrap();
+    assert_eq!(parsed.to_string(), graph.to_string());
+    assert!(parse_network("in1 -> S1 (60); S1 -> out1 (30), out2 (30) overflow").is_err());
+
+    // a loaded priority network solves to the rates it was saved with
+    let solution = solve_flow(&parsed, &balancer.inputs, balancer.max_belt).unwrap();
+    let rates = parsed.edges.iter().map(|e| Rational::from_scaled(e.rate)).collect::<Vec<_>>();
+    assert_eq!(solution.rates, rates);
+    let short = solve_flow(&parsed, &[100 * 100_000_000], balancer.max_belt).err().unwrap();
+    assert!(short.contains("gets less than"));
+    let sorter = parse_network("in1 -> F1 (60); F1 -> out1 (30), out2 (30)").unwrap();
+    let error = solve_flow(&sorter, &[60 * 100_000_000], balancer.max_belt).err().unwrap();
+    assert!(error.contains("programmable splitter"));
+}
+
+#[test]
+fn test_saturated_merge() {
+    // without -saturate more input than output can't be balanced
+    let balancer = Balancer::new(Args::new("-in 600 600 -out 390 390 -mb 780".to_string()));
+    assert!(balancer.find_ideal_balance().path().is_empty());
+
+    let balancer = Balancer::new(Args::new("-in 600 600 -out 390 390 -mb 780 -saturate".to_string()));
+    let result = balancer.find_ideal_balance();
+    assert!(matches!(result.path()[0].0, Operation::Merge { output: 78_000_000_000, .. }));
+
+    let report = balancer.saturation(&result).unwrap();
+    assert!(report.matches());
+    assert_eq!(report.taken, [39_000_000_000, 39_000_000_000]);
+    assert_eq!(report.full, [("M1".to_string(), "S1".to_string())]);
+
+    // designs that pass on every item can't throttle a surplus, -saturate is dropped
+    let args = Args::new("-in 600 600 -out 390 390 -mb 780 -saturate -strict".to_string());
+    assert!(!args.saturate);
+    assert!(Balancer::new(args).find_ideal_balance().path().is_empty());
+    let args = Args::new("-in iron:600 iron:600 -out iron:390 iron:390 -mb 780 -saturate".to_string());
+    assert!(!args.saturate);
+    assert!(!Args::new("-in 600 600 -out a=390!1 b=390 -mb 780 -saturate".to_string()).saturate);
+}
+
+#[test]
+fn test_burst_source() {
+    let args = Args::new("-in 120 120 -out 80 80 80 -mb 300 -burst 1@480/2400".to_string());
+    let bursts = args.bursts.clone();
+    assert_eq!(bursts, [(1, 480.0, 2400.0)]);
+    let balancer = Balancer::new(args);
+    let result = balancer.find_ideal_balance();
+    assert!(balancer.burst_sources(&[(1, 60.0, 2400.0)]).is_err());
+    assert!(balancer.burst_sources(&[(3, 480.0, 2400.0)]).is_err());
+    assert!(balancer.burst_sources(&[(1, 480.0, 2400.0), (1, 600.0, 100.0)]).is_err());
+    assert!(balancer.burst_sources(&[(1, 480.0, 2400.0), (2, 600.0, 100.0)]).is_ok());
+
+    let sources = balancer.burst_sources(&bursts).unwrap();
+    assert_eq!(sources[0].minutes(), (5.0, 20.0));
+    let report = balancer.bursts(&result, sources).unwrap();
+    // the 360/min over its average for the 5 minutes a burst lasts has to be
+    // buffered somewhere, outputs take it in their share of input 1
+    assert!((report.buffers.iter().sum::<f64>() - 1800.0).abs() < 1e-6);
+    assert!(report.peak.iter().sum::<u64>().abs_diff(60_000_000_000) <= 100_000_000);
+    // the input belt itself can't carry the peak
+    assert_eq!(report.overloaded[0].0, "in1");
+}
+
+#[test]
+fn test_grid_layout() {
+    let next_to = |a: (usize, usize), b: (usize, usize)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1;
+    for problem in ["-in 120 120 -out 80 80 80", "-in 480 -out 60 60 60 60 60 60 60 60", "-in iron:60+copper:30 -out iron:30 iron:30+copper:30"] {
+        let balancer = Balancer::new(Args::new(problem.to_string()));
+        let result = balancer.find_ideal_balance();
+        let graph = balancer.graph(&result).unwrap();
+        let layout = balancer.layout(&result).unwrap();
+
+        let mut used = HashMap::new();
+        for (n, &at) in layout.positions.iter().enumerate() {
+            assert_eq!(layout.grid[at.1][at.0], Cell::Node(n));
+        }
+        for (edge, route) in graph.edges.iter().zip(&layout.routes) {
+            // every belt runs unbroken from the building it leaves to the one it feeds
+            assert!(next_to(layout.positions[edge.from], route[0]));
+            assert!(next_to(*route.last().unwrap(), layout.positions[edge.to]));
+            assert!(route.windows(2).all(|w| next_to(w[0], w[1])));
+            for &at in route {
+                *used.entry(at).or_insert(0) += 1;
+            }
+        }
+        // belts only share a foundation where one goes over the other
+        for (at, count) in used {
+            let crossing = layout.grid[at.1][at.0] == Cell::Crossing;
+            assert_eq!(count, if crossing { 2 } else { 1 });
+        }
+        assert_eq!(layout.belt_length(), layout.routes.iter().map(|r| (r.len() + 1) * 8).sum::<usize>());
+    }
+
+    // a programmable splitter's left belt leaves from its north side
+    let balancer = Balancer::new(Args::new("-in iron:60+copper:30 -out iron:60 copper:30".to_string()));
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+    let layout = balancer.layout(&result).unwrap();
+    let filter = graph.nodes.iter().position(|n| n.kind == NodeKind::Filter).unwrap();
+    let left = graph.outgoing(filter)[0];
+    let (x, y) = layout.positions[filter];
+    assert_eq!(layout.routes[left][0], (x, y - 1));
+}
+
+#[test]
+fn test_floor_plan() {
+    let args = Args::new("-in 480 -out 60 60 60 60 60 60 60 60 -floor 12x40".to_string());
+    assert_eq!(args.floor, Some((12, 40)));
+    let balancer = Balancer::new(args);
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+
+    // on one floor it takes more room than allowed
+    assert!(balancer.layout(&result).unwrap().width() > 12);
+    let plan = balancer.floor_plan(&result, (12, 40)).unwrap();
+    assert!(plan.floors.len() > 1);
+    assert!(plan.width() <= 12 && plan.height() <= 40);
+    assert!(plan.floors.iter().all(|floor| floor.width() == plan.width() && floor.height() == plan.height()));
+
+    // a belt between floors turns into one belt up to a lift and one off it
+    let belts = plan.floors.iter().map(|floor| floor.routes.len()).sum::<usize>();
+    assert_eq!(belts, graph.edges.len() + plan.lifts.len());
+    for lift in &plan.lifts {
+        assert!(lift.from < lift.to);
+        assert_eq!(lift.height(), (lift.to - lift.from) * crate::floors::FLOOR_HEIGHT);
+        // the lift stands on the same foundation of every floor it passes
+        for floor in &plan.floors[lift.from..=lift.to] {
+            let (x, y) = lift.at;
+            let Cell::Node(n) = floor.grid[y][x] else { panic!("no lift at {:?}", lift.at) };
+            assert_eq!(floor.names[n], lift.name);
+        }
+    }
+
+    // with room to spare it stays on one floor
+    let plan = balancer.floor_plan(&result, (40, 40)).unwrap();
+    assert_eq!(plan.floors.len(), 1);
+    assert!(plan.lifts.is_empty());
+    assert!(balancer.floor_plan(&result, (3, 3)).is_none());
+}
+
+#[test]
+fn test_svg_diagram() {
+    assert_eq!(tier(6_000_000_000), 0);
+    assert_eq!(tier(6_100_000_000), 1);
+    assert_eq!(tier(120_000_000_000), 5);
+    assert_eq!(tier(130_000_000_000), 6);
+
+    let args = Args::new("-in 480 -out 60 60 60 60 60 60 60 60 -svg plan.svg".to_string());
+    assert_eq!(args.svg.as_deref(), Some("plan.svg"));
+    let balancer = Balancer::new(args);
+    let result = balancer.find_ideal_balance();
+    let graph = balancer.graph(&result).unwrap();
+
+    let diagram = Diagram::new(&graph).unwrap();
+    // every piece of belt goes from one layer to the next
+    let layer = |v| diagram.layers.iter().position(|layer| layer.contains(&v)).unwrap();
+    for chain in &diagram.chains {
+        assert!(chain.windows(2).all(|w| layer(w[1]) == layer(w[0]) + 1));
+    }
+    // a splitter tree can be drawn without belts crossing
+    assert_eq!(diagram.crossings(), 0);
+
+    let image = balancer.svg(&result).unwrap();
+    assert!(image.starts_with("<svg") && image.ends_with("</svg>"));
+    assert_eq!(image.matches("<path").count(), graph.edges.len());
+    for node in &graph.nodes {
+        assert!(image.contains(&format!(">{}<", node.name())));
+    }
+    // 480 and 240 need a mk4 belt, the rest fit on mk2 and mk1
+    assert!(image.contains("mk4 belt, up to 480/min"));
+    assert!(image.contains("mk1 belt, up to 60/min"));
+}